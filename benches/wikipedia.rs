@@ -29,27 +29,101 @@ fn rust_wikipedia(c: &mut Criterion) {
 			})
 			.collect();
 
+	// Compile the stylesheet's selectors once, rather than on every `select` call.
+	let compiled: Vec<kuchikiki::Selectors> = selectors
+		.iter()
+		.filter_map(|selector| kuchikiki::Selectors::compile(selector).ok())
+		.collect();
+
 	c.bench_function("select", move |b| {
 		b.iter(|| {
 			let document = kuchikiki::parse_html().one(black_box(html));
 
 			for selector in black_box(&selectors) {
-				match document.select(selector) {
-					Ok(iter) => {
-						for item in iter {
-							black_box(item);
-						}
+				if let Ok(iter) = document.select(selector) {
+					for item in iter {
+						black_box(item);
 					}
-					Err(_) => {}
 				}
 			}
 		})
 	});
+
+	c.bench_function("select_compiled", move |b| {
+		b.iter(|| {
+			let document = kuchikiki::parse_html().one(black_box(html));
+
+			for selectors in black_box(&compiled) {
+				for item in document.select_compiled(selectors) {
+					black_box(item);
+				}
+			}
+		})
+	});
+}
+
+fn repeated_selector(c: &mut Criterion) {
+	let fragments: Vec<_> = (0..10_000)
+		.map(|i| {
+			kuchikiki::parse_html().one(format!(
+				r#"<div class="item"><p class="title">{}</p><p class="body">text</p></div>"#,
+				i
+			))
+		})
+		.collect();
+
+	let mut group = c.benchmark_group("repeated_selector");
+	group.bench_function("select", |b| {
+		b.iter(|| {
+			for fragment in &fragments {
+				for item in fragment.select(black_box("div.item > p.title")).unwrap() {
+					black_box(item);
+				}
+			}
+		})
+	});
+	group.bench_function("select_compiled", |b| {
+		let selectors = kuchikiki::Selectors::compile("div.item > p.title").unwrap();
+		b.iter(|| {
+			for fragment in &fragments {
+				for item in fragment.select_compiled(black_box(&selectors)) {
+					black_box(item);
+				}
+			}
+		})
+	});
+	group.finish();
+}
+
+fn select_first(c: &mut Criterion) {
+	let html = format!(
+		r#"<p id="target">first</p>{}"#,
+		"<div><p>text</p></div>".repeat(100_000)
+	);
+	let document = kuchikiki::parse_html().one(html);
+
+	let mut group = c.benchmark_group("select_first");
+	group.bench_function("select_first", |b| {
+		b.iter(|| black_box(document.select_first(black_box("p")).unwrap()))
+	});
+	group.bench_function("select_next", |b| {
+		b.iter(|| black_box(document.select(black_box("p")).unwrap().next()))
+	});
+	group.bench_function("select_count", |b| {
+		b.iter(|| black_box(document.select_count(black_box("p")).unwrap()))
+	});
+	group.bench_function("select_n", |b| {
+		b.iter(|| black_box(document.select_n(black_box("p"), 5).unwrap()))
+	});
+	group.bench_function("select_collect", |b| {
+		b.iter(|| black_box(document.select(black_box("p")).unwrap().collect::<Vec<_>>()))
+	});
+	group.finish();
 }
 
 criterion_group! {
 	name = benches;
 	config = Criterion::default().measurement_time(Duration::from_secs(25));
-	targets = rust_wikipedia
+	targets = rust_wikipedia, repeated_selector, select_first
 }
 criterion_main!(benches);
@@ -2,6 +2,19 @@ use fastbloom::BloomFilter;
 use html5ever::{LocalName, Namespace, Prefix};
 use indexmap::{map::Entry, IndexMap};
 use selectors::attr::{CaseSensitivity, SELECTOR_WHITESPACE};
+use std::iter::FromIterator;
+
+/// Bloom filter bits allocated per class, which keeps the false-positive rate well under 1%.
+const BLOOM_BITS_PER_CLASS: usize = 16;
+
+/// Upper bound on the size of a class Bloom filter, for elements with very many classes.
+const BLOOM_MAX_BITS: usize = 1024;
+
+/// Seed for hashing classes into Bloom filters.
+///
+/// A fixed seed makes probes reproducible and saves seeding each filter from the system's
+/// random source. The filters only speed up matching, so predictable hashes are harmless.
+const BLOOM_SEED: u128 = 0x006b_7563_6869_6b69_6b69;
 
 #[derive(Debug, Clone)]
 pub(crate) enum ClassCache {
@@ -10,37 +23,60 @@ pub(crate) enum ClassCache {
 	/// filter for a quick initial check. If positive, we do an actual check. This two-tier
 	/// approach ensures fewer actual checks on class attributes.
 	Bloom(BloomFilter),
-	/// Element has a single class.
+	/// Element has a single class, or none.
 	Single,
 }
 
 impl ClassCache {
 	fn new(value: &str) -> Self {
-		if !value.trim().contains(SELECTOR_WHITESPACE) {
-			// We just have a single class and a Bloom filter is not needed.
+		if !value
+			.trim_matches(SELECTOR_WHITESPACE)
+			.contains(SELECTOR_WHITESPACE)
+		{
+			// We just have a single class, perhaps with surrounding whitespace,
+			// and a Bloom filter is not needed.
 			ClassCache::Single
 		} else {
-			// Build a Bloom filter for all element's classes
-			let classes: Vec<_> = value
-				.split(SELECTOR_WHITESPACE)
-				.filter(|s| !s.is_empty())
+			// Build a Bloom filter for all element's classes.
+			// Classes are hashed as ASCII-lowercased bytes, so that both case-sensitive and
+			// ASCII case-insensitive queries can probe it.
+			let classes: Vec<_> = split_tokens(value)
+				.map(|class| class.as_bytes().to_ascii_lowercase())
 				.collect();
-			ClassCache::Bloom(BloomFilter::with_num_bits(64).items(classes))
+			let num_bits = (classes.len() * BLOOM_BITS_PER_CLASS).clamp(64, BLOOM_MAX_BITS);
+			ClassCache::Bloom(
+				BloomFilter::with_num_bits(num_bits)
+					.seed(&BLOOM_SEED)
+					.items(classes),
+			)
 		}
 	}
 }
 
+/// Split a space-separated token list, such as a `class` attribute value, into its tokens.
+fn split_tokens(value: &str) -> impl Iterator<Item = &str> {
+	value
+		.split(SELECTOR_WHITESPACE)
+		.filter(|token| !token.is_empty())
+}
+
 /// Convenience wrapper around a indexmap that adds method for attributes in the null namespace.
 #[derive(Debug, Clone)]
 pub struct Attributes {
 	/// A map of attributes whose name can have namespaces.
+	///
+	/// Changing the `class` attribute directly through the map leaves the class cache stale;
+	/// prefer the methods on `Attributes`, which keep it in sync.
 	pub map: IndexMap<ExpandedName, Attribute>,
 	/// The 'class' attribute value is separated for performance reasons.
 	pub(crate) class_cache: Option<ClassCache>,
 }
 
 impl Attributes {
-	pub(crate) fn new<I>(attributes: I) -> Attributes
+	/// Create attributes from names and attributes, in order.
+	///
+	/// If a name appears more than once, the last value is kept at the first position.
+	pub fn new<I>(attributes: I) -> Attributes
 	where
 		I: IntoIterator<Item = (ExpandedName, Attribute)>,
 	{
@@ -52,6 +88,24 @@ impl Attributes {
 		Attributes { map, class_cache }
 	}
 
+	/// Create attributes in the null namespace, without prefixes, from local names and values.
+	pub fn from_local_names<I, A, V>(attributes: I) -> Attributes
+	where
+		I: IntoIterator<Item = (A, V)>,
+		A: Into<LocalName>,
+		V: Into<String>,
+	{
+		Attributes::new(attributes.into_iter().map(|(local_name, value)| {
+			(
+				ExpandedName::new(ns!(), local_name),
+				Attribute {
+					prefix: None,
+					value: value.into(),
+				},
+			)
+		}))
+	}
+
 	/// Manually check whether the class attribute value contains the given class.
 	#[inline]
 	fn has_class_impl(&self, name: &[u8], case_sensitivity: CaseSensitivity) -> bool {
@@ -67,14 +121,38 @@ impl Attributes {
 		false
 	}
 
+	/// Recompute `class_cache` from the current value of the `class` attribute.
+	pub(crate) fn update_class_cache(&mut self) {
+		self.class_cache = self
+			.map
+			.get(&ExpandedName::new(ns!(), local_name!("class")))
+			.map(|attr| ClassCache::new(&attr.value));
+	}
+
+	/// Return a handle for reading and editing the tokens of the `class` attribute.
+	///
+	/// Edits made through the handle keep the internal class cache in sync.
+	#[inline]
+	pub fn class_list(&mut self) -> ClassList<'_> {
+		ClassList { attributes: self }
+	}
+
 	#[inline]
 	pub(crate) fn has_class(&self, name: &[u8], case_sensitivity: CaseSensitivity) -> bool {
 		match (&self.class_cache, case_sensitivity) {
-			(Some(ClassCache::Single), case_sensitivity) => self
-				.get(local_name!("class"))
-				.map_or(false, |class| case_sensitivity.eq(class.as_bytes(), name)),
-			(Some(ClassCache::Bloom(bloom_filter)), CaseSensitivity::CaseSensitive) => {
-				if bloom_filter.contains(name) {
+			(Some(ClassCache::Single), case_sensitivity) => {
+				self.get(local_name!("class")).is_some_and(|class| {
+					let class = class.trim_matches(SELECTOR_WHITESPACE);
+					case_sensitivity.eq(class.as_bytes(), name)
+				})
+			}
+			(Some(ClassCache::Bloom(bloom_filter)), case_sensitivity) => {
+				let in_filter = if name.iter().any(u8::is_ascii_uppercase) {
+					bloom_filter.contains(&name.to_ascii_lowercase())
+				} else {
+					bloom_filter.contains(name)
+				};
+				if in_filter {
 					self.has_class_impl(name, case_sensitivity)
 				} else {
 					// Class is not in the Bloom filter, hence this `class` value does not
@@ -82,19 +160,133 @@ impl Attributes {
 					false
 				}
 			}
-			(Some(ClassCache::Bloom(_)), CaseSensitivity::AsciiCaseInsensitive) => {
-				self.has_class_impl(name, case_sensitivity)
-			}
 			(None, case_sensitivity) => self.has_class_impl(name, case_sensitivity),
 		}
 	}
 }
+/// A mutable view of the whitespace-separated tokens of a `class` attribute,
+/// like the DOM `classList`.
+///
+/// Obtained from [`Attributes::class_list`].
+/// Class names passed to its methods are expected to be single, non-empty tokens.
+#[derive(Debug)]
+pub struct ClassList<'a> {
+	attributes: &'a mut Attributes,
+}
+
+impl<'a> ClassList<'a> {
+	/// Return whether the `class` attribute contains the given class.
+	#[inline]
+	pub fn contains(&self, class: &str) -> bool {
+		self.attributes
+			.has_class(class.as_bytes(), CaseSensitivity::CaseSensitive)
+	}
+
+	/// Return an iterator over the classes, in attribute order.
+	#[inline]
+	pub fn iter(&self) -> impl Iterator<Item = &str> {
+		self.attributes.tokens(local_name!("class"))
+	}
+
+	/// Add a class if it is not already present.
+	pub fn add(&mut self, class: &str) {
+		if class.is_empty() || self.contains(class) {
+			return;
+		}
+		let mut value: String = self.iter().collect::<Vec<_>>().join(" ");
+		if !value.is_empty() {
+			value.push(' ');
+		}
+		value.push_str(class);
+		self.set(value);
+	}
+
+	/// Remove a class. Return whether it was present.
+	///
+	/// The `class` attribute itself is kept, even when it becomes empty.
+	pub fn remove(&mut self, class: &str) -> bool {
+		if !self.contains(class) {
+			return false;
+		}
+		let value = self
+			.iter()
+			.filter(|&c| c != class)
+			.collect::<Vec<_>>()
+			.join(" ");
+		self.set(value);
+		true
+	}
+
+	/// Remove the class if it is present, add it otherwise.
+	/// Return whether the class is present afterwards.
+	pub fn toggle(&mut self, class: &str) -> bool {
+		if self.remove(class) {
+			false
+		} else {
+			self.add(class);
+			true
+		}
+	}
+
+	fn set(&mut self, value: String) {
+		match self.attributes.entry(local_name!("class")) {
+			Entry::Occupied(mut entry) => entry.get_mut().value = value,
+			Entry::Vacant(entry) => {
+				entry.insert(Attribute {
+					prefix: None,
+					value,
+				});
+			}
+		}
+		self.attributes.update_class_cache();
+	}
+}
+
 impl PartialEq for Attributes {
 	fn eq(&self, other: &Self) -> bool {
 		self.map == other.map
 	}
 }
 
+/// Add attributes in order, replacing the value of any that are already present
+/// while keeping their position.
+impl Extend<(ExpandedName, Attribute)> for Attributes {
+	fn extend<I: IntoIterator<Item = (ExpandedName, Attribute)>>(&mut self, attributes: I) {
+		let mut class_changed = false;
+		for (name, attribute) in attributes {
+			class_changed |= name.is_class();
+			self.map.insert(name, attribute);
+		}
+		if class_changed {
+			self.update_class_cache();
+		}
+	}
+}
+
+impl Default for Attributes {
+	fn default() -> Self {
+		Attributes::new(None)
+	}
+}
+
+impl FromIterator<(ExpandedName, Attribute)> for Attributes {
+	fn from_iter<I: IntoIterator<Item = (ExpandedName, Attribute)>>(attributes: I) -> Self {
+		Attributes::new(attributes)
+	}
+}
+
+impl Attributes {
+	/// Copy every attribute of `other` into these attributes, as with `extend`.
+	pub fn merge_from(&mut self, other: &Attributes) {
+		self.extend(
+			other
+				.map
+				.iter()
+				.map(|(name, attribute)| (name.clone(), attribute.clone())),
+		);
+	}
+}
+
 /// <https://www.w3.org/TR/REC-xml-names/#dt-expname>
 #[derive(Debug, PartialEq, Eq, Hash, Clone, PartialOrd, Ord)]
 pub struct ExpandedName {
@@ -112,6 +304,11 @@ impl ExpandedName {
 			local: local.into(),
 		}
 	}
+
+	#[inline]
+	fn is_class(&self) -> bool {
+		self.ns == ns!() && self.local == local_name!("class")
+	}
 }
 
 /// The non-identifying parts of an attribute
@@ -137,15 +334,31 @@ impl Attributes {
 	}
 
 	/// Like IndexMap::get_mut
+	///
+	/// The returned reference can change the `class` attribute behind the class cache’s back,
+	/// so getting `class` this way discards the cache.
+	/// Class matching stays correct but falls back to the slower path
+	/// until the attribute is next set with `insert`.
 	pub fn get_mut<A: Into<LocalName>>(&mut self, local_name: A) -> Option<&mut String> {
-		self.map
-			.get_mut(&ExpandedName::new(ns!(), local_name))
-			.map(|attr| &mut attr.value)
+		let name = ExpandedName::new(ns!(), local_name);
+		if name.is_class() {
+			self.class_cache = None;
+		}
+		self.map.get_mut(&name).map(|attr| &mut attr.value)
 	}
 
 	/// Like IndexMap::entry
-	pub fn entry<A: Into<LocalName>>(&mut self, local_name: A) -> Entry<ExpandedName, Attribute> {
-		self.map.entry(ExpandedName::new(ns!(), local_name))
+	///
+	/// As with `get_mut`, getting the `class` entry discards the class cache.
+	pub fn entry<A: Into<LocalName>>(
+		&mut self,
+		local_name: A,
+	) -> Entry<'_, ExpandedName, Attribute> {
+		let name = ExpandedName::new(ns!(), local_name);
+		if name.is_class() {
+			self.class_cache = None;
+		}
+		self.map.entry(name)
 	}
 
 	/// Like IndexMap::insert
@@ -154,17 +367,260 @@ impl Attributes {
 		local_name: A,
 		value: String,
 	) -> Option<Attribute> {
-		self.map.insert(
-			ExpandedName::new(ns!(), local_name),
+		let name = ExpandedName::new(ns!(), local_name);
+		let is_class = name.is_class();
+		let previous = self.map.insert(
+			name,
 			Attribute {
 				prefix: None,
 				value,
 			},
-		)
+		);
+		if is_class {
+			self.update_class_cache();
+		}
+		previous
 	}
 
 	/// Like IndexMap::remove
 	pub fn remove<A: Into<LocalName>>(&mut self, local_name: A) -> Option<Attribute> {
-		self.map.swap_remove(&ExpandedName::new(ns!(), local_name))
+		let name = ExpandedName::new(ns!(), local_name);
+		let removed = self.map.swap_remove(&name);
+		if name.is_class() {
+			self.class_cache = None;
+		}
+		removed
+	}
+
+	/// Add the attribute with an empty value if it is absent, or remove it if it is present,
+	/// like the DOM `toggleAttribute`. Return whether the attribute is present afterwards.
+	///
+	/// With `Some(force)`, the attribute is only added if `force` is true,
+	/// and only removed if it is false. An attribute that stays present keeps its value.
+	/// Toggling `class` keeps the class cache up to date.
+	pub fn toggle<A: Into<LocalName>>(&mut self, local_name: A, force: Option<bool>) -> bool {
+		let local_name = local_name.into();
+		let present = self.contains(local_name.clone());
+		let wanted = force.unwrap_or(!present);
+		if wanted && !present {
+			self.insert(local_name, String::new());
+		} else if !wanted && present {
+			self.remove(local_name);
+		}
+		wanted
+	}
+
+	/// Like `get`, without leading and trailing ASCII whitespace.
+	pub fn get_trimmed<A: Into<LocalName>>(&self, local_name: A) -> Option<&str> {
+		self.get(local_name)
+			.map(|value| value.trim_matches(SELECTOR_WHITESPACE))
+	}
+
+	/// Return an iterator over the space-separated tokens of an attribute, in order,
+	/// such as the link types of `rel`.
+	///
+	/// Tokens are split on ASCII whitespace, as for classes. A missing attribute has no tokens.
+	pub fn tokens<A: Into<LocalName>>(&self, local_name: A) -> impl Iterator<Item = &str> {
+		split_tokens(self.get(local_name).unwrap_or(""))
+	}
+
+	/// Return an iterator over the attributes, in document order.
+	pub fn iter(&self) -> impl Iterator<Item = (&ExpandedName, &Attribute)> {
+		self.map.iter()
+	}
+
+	/// Return an iterator over the attributes with mutable values, in document order.
+	///
+	/// As with `get_mut`, this discards the class cache.
+	pub fn iter_mut(&mut self) -> impl Iterator<Item = (&ExpandedName, &mut Attribute)> {
+		self.class_cache = None;
+		self.map.iter_mut()
+	}
+
+	/// Return the number of attributes.
+	pub fn len(&self) -> usize {
+		self.map.len()
+	}
+
+	/// Return whether there are no attributes.
+	pub fn is_empty(&self) -> bool {
+		self.map.is_empty()
+	}
+}
+
+impl Attributes {
+	/// Like IndexMap::contains, for an attribute in the given namespace
+	pub fn contains_ns<N: Into<Namespace>, A: Into<LocalName>>(
+		&self,
+		namespace: N,
+		local_name: A,
+	) -> bool {
+		self.map
+			.contains_key(&ExpandedName::new(namespace, local_name))
+	}
+
+	/// Like IndexMap::get, for an attribute in the given namespace
+	pub fn get_ns<N: Into<Namespace>, A: Into<LocalName>>(
+		&self,
+		namespace: N,
+		local_name: A,
+	) -> Option<&str> {
+		self.map
+			.get(&ExpandedName::new(namespace, local_name))
+			.map(|attr| &*attr.value)
+	}
+
+	/// Like IndexMap::insert, for an attribute in the given namespace.
+	///
+	/// The prefix is used when serializing, e.g. `Some(namespace_prefix!("xlink"))`
+	/// for `xlink:href`.
+	pub fn insert_ns<N: Into<Namespace>, A: Into<LocalName>>(
+		&mut self,
+		namespace: N,
+		prefix: Option<Prefix>,
+		local_name: A,
+		value: String,
+	) -> Option<Attribute> {
+		let name = ExpandedName::new(namespace, local_name);
+		let is_class = name.is_class();
+		let previous = self.map.insert(name, Attribute { prefix, value });
+		if is_class {
+			self.update_class_cache();
+		}
+		previous
+	}
+
+	/// Like IndexMap::remove, for an attribute in the given namespace
+	pub fn remove_ns<N: Into<Namespace>, A: Into<LocalName>>(
+		&mut self,
+		namespace: N,
+		local_name: A,
+	) -> Option<Attribute> {
+		let name = ExpandedName::new(namespace, local_name);
+		let removed = self.map.swap_remove(&name);
+		if name.is_class() {
+			self.class_cache = None;
+		}
+		removed
+	}
+
+	/// Return an iterator over the attributes in the given namespace, in document order.
+	///
+	/// For example, `iter_ns(&ns!(xlink))` yields `xlink:href`,
+	/// and `iter_ns(&ns!(xmlns))` yields `xmlns` and `xmlns:*` namespace declarations,
+	/// which the HTML parser keeps as attributes.
+	pub fn iter_ns<'a>(
+		&'a self,
+		namespace: &'a Namespace,
+	) -> impl Iterator<Item = (&'a ExpandedName, &'a Attribute)> + 'a {
+		self.map
+			.iter()
+			.filter(move |(name, _)| name.ns == *namespace)
+	}
+
+	/// Return an iterator over the attributes written with the given prefix, in document order.
+	///
+	/// Prefixes are only kept for serialization: prefer `iter_ns`,
+	/// which also finds attributes put in the same namespace with another prefix.
+	pub fn iter_prefix<'a>(
+		&'a self,
+		prefix: &'a Prefix,
+	) -> impl Iterator<Item = (&'a ExpandedName, &'a Attribute)> + 'a {
+		self.map
+			.iter()
+			.filter(move |(_, attr)| attr.prefix.as_ref() == Some(prefix))
+	}
+
+	/// Rename an attribute, keeping its value, prefix and position among the attributes.
+	///
+	/// An existing attribute named `to` is replaced.
+	/// Return whether an attribute named `from` was present.
+	pub fn rename(&mut self, from: ExpandedName, to: ExpandedName) -> bool {
+		let Some(index) = self.map.get_index_of(&from) else {
+			return false;
+		};
+		if from == to {
+			return true;
+		}
+		let (_, attribute) = self.map.shift_remove_index(index).unwrap();
+		let index = match self.map.get_index_of(&to) {
+			Some(existing) => {
+				self.map.shift_remove_index(existing);
+				if existing < index {
+					index - 1
+				} else {
+					index
+				}
+			}
+			None => index,
+		};
+		let changes_class = from.is_class() || to.is_class();
+		self.map.shift_insert(index, to, attribute);
+		if changes_class {
+			self.update_class_cache();
+		}
+		true
+	}
+}
+
+impl Attributes {
+	/// Return the value of a `data-*` attribute, like `element.dataset[key]` in the DOM.
+	///
+	/// The key is in camelCase: `fooBar` reads the `data-foo-bar` attribute.
+	pub fn get_data(&self, key: &str) -> Option<&str> {
+		self.get(data_attribute_name(key))
+	}
+
+	/// Set a `data-*` attribute, like assigning to `element.dataset[key]` in the DOM.
+	///
+	/// The key is in camelCase: `fooBar` sets the `data-foo-bar` attribute.
+	pub fn set_data(&mut self, key: &str, value: String) -> Option<Attribute> {
+		self.insert(data_attribute_name(key), value)
+	}
+
+	/// Remove a `data-*` attribute, like `delete element.dataset[key]` in the DOM.
+	pub fn remove_data(&mut self, key: &str) -> Option<Attribute> {
+		self.remove(data_attribute_name(key))
+	}
+
+	/// Return an iterator over the `data-*` attributes as camelCase keys and values,
+	/// in attribute order.
+	pub fn dataset(&self) -> impl Iterator<Item = (String, &str)> {
+		self.map.iter().filter_map(|(name, attr)| {
+			if name.ns != ns!() {
+				return None;
+			}
+			let key = name.local.strip_prefix("data-")?;
+			Some((data_key(key), &*attr.value))
+		})
+	}
+}
+
+/// Convert a camelCase dataset key to the name of its `data-*` attribute.
+fn data_attribute_name(key: &str) -> String {
+	let mut name = String::with_capacity("data-".len() + key.len() + 2);
+	name.push_str("data-");
+	for c in key.chars() {
+		if c.is_ascii_uppercase() {
+			name.push('-');
+			name.push(c.to_ascii_lowercase());
+		} else {
+			name.push(c);
+		}
+	}
+	name
+}
+
+/// Convert the part of a `data-*` attribute name after `data-` to a camelCase dataset key.
+fn data_key(name: &str) -> String {
+	let mut key = String::with_capacity(name.len());
+	let mut chars = name.chars().peekable();
+	while let Some(c) = chars.next() {
+		if c == '-' && chars.peek().is_some_and(char::is_ascii_lowercase) {
+			key.extend(chars.next().map(|c| c.to_ascii_uppercase()));
+		} else {
+			key.push(c);
+		}
 	}
+	key
 }
@@ -0,0 +1,123 @@
+use html5ever::{LocalName, Namespace, QualName};
+use indexmap::IndexMap;
+
+use crate::attributes::{Attribute, ExpandedName};
+use crate::tree::NodeRef;
+
+impl NodeRef {
+	/// Start building an HTML element with the given local name,
+	/// such as `NodeRef::element("a").attr("href", "/").text("Home").build()`.
+	#[inline]
+	pub fn element<L: Into<LocalName>>(local_name: L) -> ElementBuilder {
+		ElementBuilder::new(QualName::new(None, ns!(html), local_name.into()))
+	}
+}
+
+/// A builder for an element node, with its attributes and children.
+///
+/// Attributes are kept in the order they are first set.
+#[derive(Debug)]
+pub struct ElementBuilder {
+	name: QualName,
+	attributes: IndexMap<ExpandedName, Attribute>,
+	children: Vec<NodeRef>,
+}
+
+impl ElementBuilder {
+	/// Start building an element with the given name, in any namespace.
+	pub fn new(name: QualName) -> ElementBuilder {
+		ElementBuilder {
+			name,
+			attributes: IndexMap::new(),
+			children: Vec::new(),
+		}
+	}
+
+	/// Set an attribute in the null namespace.
+	///
+	/// Setting an attribute again replaces its value but keeps its position.
+	pub fn attr<A: Into<LocalName>, V: Into<String>>(self, local_name: A, value: V) -> Self {
+		self.attr_ns(ns!(), None, local_name, value)
+	}
+
+	/// Set an attribute in the given namespace, with an optional prefix.
+	pub fn attr_ns<N, A, V>(
+		mut self,
+		namespace: N,
+		prefix: Option<html5ever::Prefix>,
+		local_name: A,
+		value: V,
+	) -> Self
+	where
+		N: Into<Namespace>,
+		A: Into<LocalName>,
+		V: Into<String>,
+	{
+		self.attributes.insert(
+			ExpandedName::new(namespace, local_name),
+			Attribute {
+				prefix,
+				value: value.into(),
+			},
+		);
+		self
+	}
+
+	/// Add a class to the `class` attribute, creating it if needed.
+	pub fn class(mut self, class: &str) -> Self {
+		let attribute = self
+			.attributes
+			.entry(ExpandedName::new(ns!(), local_name!("class")))
+			.or_insert_with(|| Attribute {
+				prefix: None,
+				value: String::new(),
+			});
+		if !attribute.value.is_empty() {
+			attribute.value.push(' ');
+		}
+		attribute.value.push_str(class);
+		self
+	}
+
+	/// Append a child node.
+	///
+	/// This accepts another `ElementBuilder`, which is built first.
+	pub fn child<N: Into<NodeRef>>(mut self, child: N) -> Self {
+		self.children.push(child.into());
+		self
+	}
+
+	/// Append several child nodes.
+	pub fn children<I>(mut self, children: I) -> Self
+	where
+		I: IntoIterator,
+		I::Item: Into<NodeRef>,
+	{
+		self.children.extend(children.into_iter().map(Into::into));
+		self
+	}
+
+	/// Append a text node.
+	pub fn text<T: Into<String>>(self, text: T) -> Self {
+		self.child(NodeRef::new_text(text))
+	}
+
+	/// Create the element node.
+	///
+	/// The children of a `<template>` element are appended to its template contents.
+	pub fn build(self) -> NodeRef {
+		let node = NodeRef::new_element(self.name, self.attributes);
+		let parent = node.content_root();
+		for child in self.children {
+			parent.append(child);
+		}
+		node
+	}
+}
+
+impl From<ElementBuilder> for NodeRef {
+	#[inline]
+	fn from(builder: ElementBuilder) -> NodeRef {
+		builder.build()
+	}
+}
@@ -0,0 +1,169 @@
+//! Character encoding detection for byte input, following a simplified version of
+//! the [HTML encoding sniffing algorithm](https://html.spec.whatwg.org/#encoding-sniffing-algorithm).
+
+use encoding_rs::{Encoding, UTF_8, WINDOWS_1252, X_USER_DEFINED};
+
+/// How many leading bytes are searched for a `<meta>` charset declaration.
+const PRESCAN_LENGTH: usize = 1024;
+
+/// Determine the encoding of an HTML document.
+///
+/// Return the encoding and the length of the byte order mark to skip, if any.
+///
+/// In order of precedence: a byte order mark, the explicit override given by the caller,
+/// a `<meta>` declaration in the first 1024 bytes, UTF-8 if the input is valid UTF-8,
+/// and finally windows-1252.
+pub(crate) fn sniff(
+	bytes: &[u8],
+	encoding_override: Option<&'static Encoding>,
+) -> (&'static Encoding, usize) {
+	if let Some((encoding, bom_length)) = Encoding::for_bom(bytes) {
+		return (encoding, bom_length);
+	}
+	if let Some(encoding) = encoding_override {
+		return (encoding, 0);
+	}
+	if let Some(encoding) = prescan(&bytes[..bytes.len().min(PRESCAN_LENGTH)]) {
+		return (encoding, 0);
+	}
+	if std::str::from_utf8(bytes).is_ok() {
+		(UTF_8, 0)
+	} else {
+		(WINDOWS_1252, 0)
+	}
+}
+
+/// Look for a `<meta charset>` or `<meta http-equiv=content-type content=...>` declaration.
+fn prescan(bytes: &[u8]) -> Option<&'static Encoding> {
+	let mut position = 0;
+	while let Some(start) = find_ignore_ascii_case(&bytes[position..], b"<meta") {
+		let attributes_start = position + start + b"<meta".len();
+		match bytes.get(attributes_start) {
+			Some(b) if b.is_ascii_whitespace() || *b == b'/' => {}
+			_ => {
+				position = attributes_start;
+				continue;
+			}
+		}
+		let (encoding, end) = meta_encoding(&bytes[attributes_start..]);
+		if let Some(encoding) = encoding {
+			// A declaration can only be relied on if the document is ASCII-compatible,
+			// which it is known to be at this point.
+			if encoding == encoding_rs::UTF_16LE || encoding == encoding_rs::UTF_16BE {
+				return Some(UTF_8);
+			}
+			if encoding == X_USER_DEFINED {
+				return Some(WINDOWS_1252);
+			}
+			return Some(encoding);
+		}
+		position = attributes_start + end;
+	}
+	None
+}
+
+/// Parse the attributes of a `<meta>` tag up to its closing `>`.
+///
+/// Return the declared encoding, if any, and the number of bytes consumed.
+fn meta_encoding(bytes: &[u8]) -> (Option<&'static Encoding>, usize) {
+	let mut charset = None;
+	let mut content_charset = None;
+	let mut http_equiv_content_type = false;
+	let mut i = 0;
+	loop {
+		while i < bytes.len() && (bytes[i].is_ascii_whitespace() || bytes[i] == b'/') {
+			i += 1;
+		}
+		if i >= bytes.len() || bytes[i] == b'>' {
+			break;
+		}
+		let name_start = i;
+		while i < bytes.len() && !matches!(bytes[i], b'=' | b'>' | b'/') {
+			if bytes[i].is_ascii_whitespace() {
+				break;
+			}
+			i += 1;
+		}
+		let name = &bytes[name_start..i];
+		while i < bytes.len() && bytes[i].is_ascii_whitespace() {
+			i += 1;
+		}
+		let mut value: &[u8] = &[];
+		if bytes.get(i) == Some(&b'=') {
+			i += 1;
+			while i < bytes.len() && bytes[i].is_ascii_whitespace() {
+				i += 1;
+			}
+			match bytes.get(i) {
+				Some(&quote) if quote == b'"' || quote == b'\'' => {
+					let value_start = i + 1;
+					let value_end = bytes[value_start..]
+						.iter()
+						.position(|&b| b == quote)
+						.map_or(bytes.len(), |p| value_start + p);
+					value = &bytes[value_start..value_end];
+					i = (value_end + 1).min(bytes.len());
+				}
+				_ => {
+					let value_start = i;
+					while i < bytes.len() && !bytes[i].is_ascii_whitespace() && bytes[i] != b'>' {
+						i += 1;
+					}
+					value = &bytes[value_start..i];
+				}
+			}
+		}
+		if name.eq_ignore_ascii_case(b"charset") {
+			charset = charset.or_else(|| Encoding::for_label(trim_ascii(value)));
+		} else if name.eq_ignore_ascii_case(b"http-equiv") {
+			http_equiv_content_type |= trim_ascii(value).eq_ignore_ascii_case(b"content-type");
+		} else if name.eq_ignore_ascii_case(b"content") {
+			content_charset = content_charset.or_else(|| charset_from_content(value));
+		}
+	}
+	let encoding = charset.or(if http_equiv_content_type {
+		content_charset
+	} else {
+		None
+	});
+	(encoding, i)
+}
+
+/// Extract the encoding from a `content` value like `text/html; charset=Shift_JIS`.
+fn charset_from_content(content: &[u8]) -> Option<&'static Encoding> {
+	let start = find_ignore_ascii_case(content, b"charset")? + b"charset".len();
+	let rest = trim_ascii(&content[start..]);
+	let rest = trim_ascii(rest.strip_prefix(b"=")?);
+	let label = match rest.first() {
+		Some(&quote) if quote == b'"' || quote == b'\'' => {
+			let rest = &rest[1..];
+			&rest[..rest.iter().position(|&b| b == quote)?]
+		}
+		_ => {
+			let end = rest
+				.iter()
+				.position(|&b| b.is_ascii_whitespace() || b == b';')
+				.unwrap_or(rest.len());
+			&rest[..end]
+		}
+	};
+	Encoding::for_label(label)
+}
+
+fn find_ignore_ascii_case(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+	haystack
+		.windows(needle.len())
+		.position(|window| window.eq_ignore_ascii_case(needle))
+}
+
+fn trim_ascii(bytes: &[u8]) -> &[u8] {
+	let start = bytes
+		.iter()
+		.position(|b| !b.is_ascii_whitespace())
+		.unwrap_or(bytes.len());
+	let end = bytes
+		.iter()
+		.rposition(|b| !b.is_ascii_whitespace())
+		.map_or(start, |p| p + 1);
+	&bytes[start..end]
+}
@@ -1,15 +1,17 @@
 //! Node iterators
 
-// Addressing this lint is a semver-breaking change.
-// Remove this once the issue has been addressed.
-#![allow(clippy::result_unit_err)]
-
 use std::borrow::Borrow;
 use std::cell::RefCell;
-use std::iter::Rev;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::iter::{Rev, TakeWhile};
+
+use html5ever::LocalName;
+use selectors::attr::CaseSensitivity;
 
+use crate::attributes::ExpandedName;
 use crate::node_data_ref::NodeDataRef;
-use crate::select::Selectors;
+use crate::select::{MatchOpts, SelectError, Selector, SelectorError, Selectors};
 use crate::tree::{ElementData, NodeRef};
 
 impl NodeRef {
@@ -25,6 +27,32 @@ impl NodeRef {
 		Ancestors(self.parent())
 	}
 
+	/// Return an iterator of references to this node’s ancestors,
+	/// stopping before the first one for which `predicate` returns false.
+	#[inline]
+	pub fn ancestors_while<F>(&self, predicate: F) -> TakeWhile<Ancestors, F>
+	where
+		F: FnMut(&NodeRef) -> bool,
+	{
+		self.ancestors().take_while(predicate)
+	}
+
+	/// Return an iterator of references to this node’s ancestors,
+	/// stopping before the first element that matches the given selectors.
+	///
+	/// If no ancestor matches, this runs to the root like `ancestors`.
+	#[inline]
+	pub fn ancestors_until<'a>(
+		&self,
+		selectors: &'a Selectors,
+	) -> impl Iterator<Item = NodeRef> + 'a {
+		self.ancestors_while(move |node| {
+			node.clone()
+				.into_element_ref()
+				.is_none_or(|element| !selectors.matches(&element))
+		})
+	}
+
 	/// Return an iterator of references to this node and the siblings before it.
 	#[inline]
 	pub fn inclusive_preceding_siblings(&self) -> Rev<Siblings> {
@@ -101,6 +129,57 @@ impl NodeRef {
 		}
 	}
 
+	/// Return the first sibling element after this node, like the DOM `nextElementSibling`.
+	///
+	/// Text, comment and other non-element nodes are skipped.
+	#[inline]
+	pub fn next_element_sibling(&self) -> Option<NodeRef> {
+		self.following_siblings()
+			.find(|node| node.as_element().is_some())
+	}
+
+	/// Return the last sibling element before this node, like the DOM `previousElementSibling`.
+	///
+	/// Text, comment and other non-element nodes are skipped.
+	#[inline]
+	pub fn previous_element_sibling(&self) -> Option<NodeRef> {
+		self.preceding_siblings()
+			.find(|node| node.as_element().is_some())
+	}
+
+	/// Return the position of this node among its parent’s children, counting from 0.
+	///
+	/// All kinds of nodes are counted. Return `None` if this node has no parent.
+	#[inline]
+	pub fn child_index(&self) -> Option<usize> {
+		self.parent()?;
+		Some(self.preceding_siblings().count())
+	}
+
+	/// Return the position of this element among its parent’s child elements, counting from 0.
+	///
+	/// Text, comment and other non-element nodes are not counted,
+	/// so this is one less than the `n` of `:nth-child(n)`.
+	/// Return `None` if this node is not an element or has no parent.
+	#[inline]
+	pub fn element_index(&self) -> Option<usize> {
+		self.as_element()?;
+		self.parent()?;
+		Some(self.preceding_elements().count())
+	}
+
+	/// Return an iterator of this node’s sibling elements after it.
+	#[inline]
+	pub fn following_elements(&self) -> Elements<Siblings> {
+		self.following_siblings().elements()
+	}
+
+	/// Return an iterator of this node’s sibling elements before it, nearest first.
+	#[inline]
+	pub fn preceding_elements(&self) -> Elements<Rev<Siblings>> {
+		self.preceding_siblings().elements()
+	}
+
 	/// Return an iterator of references to this node’s children.
 	#[inline]
 	pub fn children(&self) -> Siblings {
@@ -114,6 +193,26 @@ impl NodeRef {
 		}
 	}
 
+	/// Return the first child element of this node, like the DOM `firstElementChild`.
+	#[inline]
+	pub fn first_element_child(&self) -> Option<NodeRef> {
+		self.children().find(|node| node.as_element().is_some())
+	}
+
+	/// Return the last child element of this node, like the DOM `lastElementChild`.
+	#[inline]
+	pub fn last_element_child(&self) -> Option<NodeRef> {
+		self.children()
+			.rev()
+			.find(|node| node.as_element().is_some())
+	}
+
+	/// Return an iterator of this node’s child elements.
+	#[inline]
+	pub fn child_elements(&self) -> Elements<Siblings> {
+		self.children().elements()
+	}
+
 	/// Return an iterator of references to this node and its descendants, in tree order.
 	///
 	/// Parent nodes appear before the descendants.
@@ -134,6 +233,69 @@ impl NodeRef {
 		Descendants(self.traverse())
 	}
 
+	/// Return an iterator of this node’s descendants, in tree order,
+	/// each with its depth below this node: 1 for children, 2 for grandchildren, and so on.
+	#[inline]
+	pub fn descendants_with_depth(&self) -> DescendantsWithDepth {
+		DescendantsWithDepth {
+			traverse: self.traverse(),
+			depth: 0,
+		}
+	}
+
+	/// Return an iterator of the text nodes among this node and its descendants, in tree order.
+	///
+	/// This is a shorthand for `inclusive_descendants().text_nodes()`.
+	#[inline]
+	pub fn text_nodes(&self) -> TextNodes<Descendants> {
+		self.inclusive_descendants().text_nodes()
+	}
+
+	/// Return an iterator of the comments among this node and its descendants, in tree order.
+	///
+	/// This is a shorthand for `inclusive_descendants().comments()`.
+	#[inline]
+	pub fn comments(&self) -> Comments<Descendants> {
+		self.inclusive_descendants().comments()
+	}
+
+	/// Return how many times each element local name appears among this node
+	/// and its descendants, including in template contents.
+	///
+	/// This is meant for auditing markup, such as to build a sanitizer allowlist.
+	/// Names in different namespaces are counted together.
+	pub fn element_name_counts(&self) -> HashMap<LocalName, usize> {
+		let mut counts = HashMap::new();
+		self.for_each_element(|element| {
+			*counts.entry(element.name.local.clone()).or_insert(0) += 1;
+		});
+		counts
+	}
+
+	/// Return how many elements among this node and its descendants, including in
+	/// template contents, have each attribute.
+	pub fn attribute_name_counts(&self) -> HashMap<ExpandedName, usize> {
+		let mut counts = HashMap::new();
+		self.for_each_element(|element| {
+			for name in element.attributes.borrow().map.keys() {
+				*counts.entry(name.clone()).or_insert(0) += 1;
+			}
+		});
+		counts
+	}
+
+	/// Call `f` with each element among this node and its descendants, in tree order,
+	/// and with the elements of template contents after the rest of the subtree.
+	fn for_each_element<F: FnMut(&ElementData)>(&self, mut f: F) {
+		let mut roots = vec![self.clone()];
+		while let Some(root) = roots.pop() {
+			for element in root.inclusive_descendants().elements() {
+				f(&element);
+				roots.extend(element.template_contents.clone());
+			}
+		}
+	}
+
 	/// Return an iterator of the start and end edges of this node and its descendants,
 	/// in tree order.
 	#[inline]
@@ -159,16 +321,309 @@ impl NodeRef {
 	}
 
 	/// Return an iterator of the inclusive descendants element that match the given selector list.
+	///
+	/// Matches are yielded in document order, each element once,
+	/// even when several selectors of the list match it, like the DOM `querySelectorAll`.
+	///
+	/// When this node is an element, `:scope` matches it, so `:scope > li`
+	/// only matches its `<li>` children.
+	#[inline]
+	pub fn select(&self, selectors: &str) -> Result<Select<Elements<Descendants>>, SelectorError> {
+		self.select_with_opts(selectors, MatchOpts::default())
+	}
+
+	/// Return an iterator of the inclusive descendants element that match the given selector list,
+	/// with the given matching options.
+	#[inline]
+	pub fn select_with_opts(
+		&self,
+		selectors: &str,
+		opts: MatchOpts,
+	) -> Result<Select<Elements<Descendants>>, SelectorError> {
+		Selectors::compile(selectors).map(|selectors| Select {
+			iter: self.inclusive_descendants().elements(),
+			selectors,
+			scope: self.selector_scope(),
+			opts,
+		})
+	}
+
+	/// Return an iterator of the inclusive descendants element that match already compiled selectors.
+	///
+	/// When matching the same selectors against many subtrees, compile them once
+	/// with `Selectors::compile` and use this method instead of `select`,
+	/// which parses the selector string on every call.
+	#[inline]
+	pub fn select_compiled<'a>(
+		&self,
+		selectors: &'a Selectors,
+	) -> Select<Elements<Descendants>, &'a Selectors> {
+		Select {
+			iter: self.inclusive_descendants().elements(),
+			selectors,
+			scope: self.selector_scope(),
+			opts: MatchOpts::default(),
+		}
+	}
+
+	/// Return the node that `:scope` matches when selecting from this node:
+	/// itself if it is an element, or else none so that `:scope` matches the root element.
+	fn selector_scope(&self) -> Option<NodeRef> {
+		self.as_element().map(|_| self.clone())
+	}
+
+	/// Return an iterator of the descendant elements with the given local name,
+	/// like the DOM `getElementsByTagName`.
+	///
+	/// The name is matched ASCII case-insensitively for elements in the HTML namespace
+	/// and exactly for other elements. `"*"` matches every element.
+	/// Unlike `select`, the name does not need to be a valid CSS identifier.
+	#[inline]
+	pub fn get_elements_by_tag_name(&self, name: &str) -> ElementsByTagName<Elements<Descendants>> {
+		ElementsByTagName::new(self.descendants().elements(), name)
+	}
+
+	/// Return an iterator of the descendant elements that have the given class,
+	/// like the DOM `getElementsByClassName` with a single class.
+	///
+	/// This checks the `class` attribute directly, without compiling a selector.
+	#[inline]
+	pub fn get_elements_by_class_name(
+		&self,
+		class: &str,
+		case_sensitivity: CaseSensitivity,
+	) -> ElementsByClassName<Elements<Descendants>> {
+		ElementsByClassName {
+			iter: self.descendants().elements(),
+			class: class.as_bytes().to_vec(),
+			case_sensitivity,
+		}
+	}
+
+	/// Return the nearest inclusive ancestor element that matches the given selector list.
+	///
+	/// Returns `Ok(None)` if no element up to the root of the tree matches.
+	/// As with `select`, `:scope` matches this node if it is an element.
 	#[inline]
-	pub fn select(&self, selectors: &str) -> Result<Select<Elements<Descendants>>, ()> {
-		self.inclusive_descendants().select(selectors)
+	pub fn closest(&self, selectors: &str) -> Result<Option<NodeRef>, SelectorError> {
+		let selectors = Selectors::compile(selectors)?;
+		let scope = self.selector_scope();
+		Ok(self
+			.inclusive_ancestors()
+			.elements()
+			.find(|element| selectors.matches_in_scope(element, scope.as_ref()))
+			.map(|element| element.as_node().clone()))
+	}
+
+	/// Return the number of inclusive descendant elements that match the given selector list,
+	/// as with `select`.
+	///
+	/// Matches are counted as they are found, without being collected,
+	/// but the whole subtree is still walked.
+	pub fn select_count(&self, selectors: &str) -> Result<usize, SelectorError> {
+		Ok(self.select(selectors)?.count())
+	}
+
+	/// Return at most the first `n` inclusive descendant elements that match the given
+	/// selector list, in document order, as with `select`.
+	///
+	/// The tree is walked lazily, so nothing after the `n`-th match is visited.
+	/// This is a shorthand for `select(selectors)?.take(n).collect()`.
+	pub fn select_n(
+		&self,
+		selectors: &str,
+		n: usize,
+	) -> Result<Vec<NodeDataRef<ElementData>>, SelectorError> {
+		Ok(self.select(selectors)?.take(n).collect())
+	}
+
+	/// Return the only inclusive descendant element that matches the given selector list.
+	///
+	/// Unlike `select_first`, finding no match or more than one is an error
+	/// that includes the selector, for code that expects exactly one element.
+	pub fn select_one(&self, selectors: &str) -> Result<NodeDataRef<ElementData>, SelectError> {
+		let mut matches = self.select(selectors)?;
+		match (matches.next(), matches.next()) {
+			(Some(element), None) => Ok(element),
+			(None, _) => Err(SelectError::NoMatch {
+				selector: selectors.to_owned(),
+			}),
+			(Some(_), Some(_)) => Err(SelectError::MultipleMatches {
+				selector: selectors.to_owned(),
+			}),
+		}
+	}
+
+	/// Return the selectors of the list that match this node, in list order.
+	///
+	/// Sort the result by `Selector::specificity` to find which one takes precedence.
+	/// Nodes other than elements match no selector.
+	pub fn matching_selectors<'a>(&self, selectors: &'a Selectors) -> Vec<&'a Selector> {
+		match self.clone().into_element_ref() {
+			Some(element) => selectors
+				.0
+				.iter()
+				.filter(|selector| selector.matches(&element))
+				.collect(),
+			None => Vec::new(),
+		}
+	}
+
+	/// Match a stylesheet against this node and its descendants.
+	///
+	/// `rules` are `(selectors, declarations)` pairs in source order.
+	/// Return each inclusive descendant element matched by at least one rule, in tree order,
+	/// along with the declarations of its matching rules in cascade order:
+	/// by increasing specificity of the most specific matching selector,
+	/// then by source order. The last declarations therefore take precedence,
+	/// which is the order to apply them in when inlining styles.
+	pub fn match_stylesheet<'a, D>(
+		&self,
+		rules: &'a [(Selectors, D)],
+	) -> Vec<(NodeRef, Vec<&'a D>)> {
+		self.inclusive_descendants()
+			.elements()
+			.filter_map(|element| {
+				let mut matched: Vec<_> = rules
+					.iter()
+					.enumerate()
+					.filter_map(|(index, (selectors, declarations))| {
+						let specificity = selectors
+							.0
+							.iter()
+							.filter(|selector| selector.matches(&element))
+							.map(Selector::specificity)
+							.max()?;
+						Some((specificity, index, declarations))
+					})
+					.collect();
+				if matched.is_empty() {
+					return None;
+				}
+				matched.sort_by_key(|&(specificity, index, _)| (specificity, index));
+				let declarations = matched
+					.into_iter()
+					.map(|(_, _, declarations)| declarations)
+					.collect();
+				Some((element.as_node().clone(), declarations))
+			})
+			.collect()
+	}
+
+	/// Return whether `other` is this node or one of its descendants, like the DOM `Node.contains`.
+	#[inline]
+	pub fn contains(&self, other: &NodeRef) -> bool {
+		other
+			.inclusive_ancestors()
+			.any(|ancestor| ancestor == *self)
+	}
+
+	/// Compare the positions of this node and `other` in tree order.
+	///
+	/// `Less` means this node comes first, which includes being an ancestor of `other`.
+	/// Return `None` if the nodes are not in the same tree.
+	/// This can be used to sort nodes in document order:
+	/// `nodes.sort_by(|a, b| a.compare_document_position(b).unwrap())`.
+	pub fn compare_document_position(&self, other: &NodeRef) -> Option<Ordering> {
+		let mut path: Vec<_> = self.inclusive_ancestors().collect();
+		let mut other_path: Vec<_> = other.inclusive_ancestors().collect();
+		path.reverse();
+		other_path.reverse();
+		if path[0] != other_path[0] {
+			return None;
+		}
+		let common = path
+			.iter()
+			.zip(&other_path)
+			.take_while(|(a, b)| a == b)
+			.count();
+		Some(match (path.get(common), other_path.get(common)) {
+			(None, None) => Ordering::Equal,
+			// One node is an ancestor of the other.
+			(None, Some(_)) => Ordering::Less,
+			(Some(_), None) => Ordering::Greater,
+			// The paths diverge at two siblings.
+			(Some(a), Some(b)) => {
+				if a.following_siblings().any(|sibling| sibling == *b) {
+					Ordering::Less
+				} else {
+					Ordering::Greater
+				}
+			}
+		})
+	}
+
+	/// Return the first inclusive descendant element whose `id` attribute is `id`, in tree order.
+	///
+	/// This walks the subtree without compiling a selector, and stops at the first match.
+	/// No index is kept, so the result always reflects the current state of the tree.
+	pub fn get_element_by_id(&self, id: &str) -> Option<NodeRef> {
+		self.inclusive_descendants().find(|node| {
+			node.as_element().is_some_and(|element| {
+				element.attributes.borrow().get(local_name!("id")) == Some(id)
+			})
+		})
 	}
 
 	/// Return the first inclusive descendants element that match the given selector list.
+	///
+	/// An invalid selector is an error, while a valid selector that matches nothing is `Ok(None)`.
+	/// The tree is walked lazily, so nothing after the first match is visited.
+	/// As with `select`, `:scope` matches this node if it is an element.
+	#[inline]
+	pub fn select_first(
+		&self,
+		selectors: &str,
+	) -> Result<Option<NodeDataRef<ElementData>>, SelectorError> {
+		Ok(self.select(selectors)?.next())
+	}
+
+	/// Return an iterator of the element children of this node that match the given selector list.
+	///
+	/// Unlike `select`, descendants below the children are not visited.
+	/// As with `select`, `:scope` matches this node if it is an element.
 	#[inline]
-	pub fn select_first(&self, selectors: &str) -> Result<NodeDataRef<ElementData>, ()> {
-		let mut elements = self.select(selectors)?;
-		elements.next().ok_or(())
+	pub fn select_children(
+		&self,
+		selectors: &str,
+	) -> Result<Select<Elements<Siblings>>, SelectorError> {
+		Selectors::compile(selectors).map(|selectors| Select {
+			iter: self.children().elements(),
+			selectors,
+			scope: self.selector_scope(),
+			opts: MatchOpts::default(),
+		})
+	}
+
+	/// Return the first element child of this node that matches the given selector list.
+	#[inline]
+	pub fn select_first_child(
+		&self,
+		selectors: &str,
+	) -> Result<Option<NodeDataRef<ElementData>>, SelectorError> {
+		Ok(self.select_children(selectors)?.next())
+	}
+
+	/// Try each of the given selector lists in order, and return the first inclusive descendant
+	/// element that matches the first list with any match, with the index of that list.
+	///
+	/// This is for fallbacks, such as when a page’s markup may come in several versions.
+	/// All selector lists are compiled before matching, so an invalid one is always an error,
+	/// even if an earlier one matches.
+	/// As with `select`, `:scope` matches this node if it is an element.
+	pub fn select_first_of(
+		&self,
+		selectors: &[&str],
+	) -> Result<Option<(usize, NodeDataRef<ElementData>)>, SelectorError> {
+		let compiled = selectors
+			.iter()
+			.map(|selectors| Selectors::compile(selectors))
+			.collect::<Result<Vec<_>, _>>()?;
+		Ok(compiled.iter().enumerate().find_map(|(i, selectors)| {
+			self.select_compiled(selectors)
+				.next()
+				.map(|element| (i, element))
+		}))
 	}
 }
 
@@ -215,6 +670,17 @@ impl DoubleEndedIterator for Siblings {
 	siblings_next!(next_back, next, previous_sibling);
 }
 
+/// Iterating over `&node` yields its children, like `node.children()`.
+impl IntoIterator for &NodeRef {
+	type Item = NodeRef;
+	type IntoIter = Siblings;
+
+	#[inline]
+	fn into_iter(self) -> Siblings {
+		self.children()
+	}
+}
+
 /// An iterator on ancestor nodes.
 #[derive(Debug, Clone)]
 pub struct Ancestors(Option<NodeRef>);
@@ -224,9 +690,8 @@ impl Iterator for Ancestors {
 
 	#[inline]
 	fn next(&mut self) -> Option<NodeRef> {
-		self.0.take().map(|node| {
+		self.0.take().inspect(|node| {
 			self.0 = node.parent();
-			node
 		})
 	}
 }
@@ -259,16 +724,41 @@ impl DoubleEndedIterator for Descendants {
 	descendants_next!(next_back);
 }
 
+/// An iterator of references to a node’s descendants, in tree order, with their depth.
+#[derive(Debug, Clone)]
+pub struct DescendantsWithDepth {
+	traverse: Traverse,
+	depth: usize,
+}
+
+impl Iterator for DescendantsWithDepth {
+	type Item = (NodeRef, usize);
+
+	#[inline]
+	fn next(&mut self) -> Option<(NodeRef, usize)> {
+		loop {
+			match self.traverse.next()? {
+				NodeEdge::Start(node) => {
+					self.depth += 1;
+					return Some((node, self.depth));
+				}
+				NodeEdge::End(_) => self.depth -= 1,
+			}
+		}
+	}
+}
+
 /// Marks either the start or the end of a node.
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum NodeEdge<T> {
-	/// Indicates that start of a node that has children.
+	/// Indicates the start of a node.
 	/// Yielded by `Traverse::next` before the node’s descendants.
 	/// In HTML or XML, this corresponds to an opening tag like `<div>`
 	Start(T),
 
-	/// Indicates that end of a node that has children.
-	/// Yielded by `Traverse::next` after the node’s descendants.
+	/// Indicates the end of a node.
+	/// Yielded by `Traverse::next` after the node’s descendants,
+	/// and right after `Start` for a node without children.
 	/// In HTML or XML, this corresponds to a closing tag like `</div>`
 	End(T),
 }
@@ -382,6 +872,10 @@ filter_map_like_iterator! {
 }
 
 /// An element iterator adaptor that yields elements maching given selectors.
+///
+/// Matching elements are yielded in the order of the underlying iterator,
+/// which for `NodeRef::select` is document order. Iterating backwards yields them in reverse.
+/// Each element is tested once, against the whole selector list.
 pub struct Select<I, S = Selectors>
 where
 	I: Iterator<Item = NodeDataRef<ElementData>>,
@@ -392,6 +886,12 @@ where
 
 	/// The selectors to be matched.
 	pub selectors: S,
+
+	/// The node that `:scope` matches, or `None` for the root element.
+	pub(crate) scope: Option<NodeRef>,
+
+	/// The options for matching the selectors.
+	pub(crate) opts: MatchOpts,
 }
 
 impl<I, S> Iterator for Select<I, S>
@@ -403,10 +903,54 @@ where
 
 	#[inline]
 	fn next(&mut self) -> Option<NodeDataRef<ElementData>> {
-		let selectors = self.selectors.borrow();
+		let (selectors, scope, opts) = (self.selectors.borrow(), self.scope.as_ref(), &self.opts);
 		self.iter
 			.by_ref()
-			.find(|element| selectors.matches(element))
+			.find(|element| selectors.matches_with_opts(element, scope, opts))
+	}
+}
+
+impl<I, S> Select<I, S>
+where
+	I: Iterator<Item = NodeDataRef<ElementData>>,
+	S: Borrow<Selectors>,
+{
+	/// Return the node that `:scope` matches, or `None` if it matches the root element.
+	pub fn scope(&self) -> Option<&NodeRef> {
+		self.scope.as_ref()
+	}
+
+	/// Return the options for matching the selectors.
+	pub fn opts(&self) -> &MatchOpts {
+		&self.opts
+	}
+
+	/// Detach every matching element from the tree, and return them.
+	///
+	/// All matches are collected before the tree is changed,
+	/// so detaching does not disturb the traversal.
+	pub fn detach_all(self) -> Vec<NodeDataRef<ElementData>> {
+		let elements: Vec<_> = self.collect();
+		for element in &elements {
+			element.as_node().detach()
+		}
+		elements
+	}
+
+	/// Replace every matching element with the node returned by `f`,
+	/// and return the replaced elements.
+	///
+	/// All matches are collected before the tree is changed.
+	/// A match nested in an earlier match is replaced within the detached earlier match.
+	pub fn replace_all_with<F>(self, mut f: F) -> Vec<NodeDataRef<ElementData>>
+	where
+		F: FnMut(&NodeDataRef<ElementData>) -> NodeRef,
+	{
+		let elements: Vec<_> = self.collect();
+		for element in &elements {
+			element.as_node().replace_with(f(element))
+		}
+		elements
 	}
 }
 
@@ -417,11 +961,151 @@ where
 {
 	#[inline]
 	fn next_back(&mut self) -> Option<NodeDataRef<ElementData>> {
-		let selectors = self.selectors.borrow();
+		let (selectors, scope, opts) = (self.selectors.borrow(), self.scope.as_ref(), &self.opts);
 		self.iter
 			.by_ref()
 			.rev()
-			.find(|element| selectors.matches(element))
+			.find(|element| selectors.matches_with_opts(element, scope, opts))
+	}
+}
+
+/// An element iterator adaptor that yields elements with a given local name.
+///
+/// See [`NodeRef::get_elements_by_tag_name`].
+pub struct ElementsByTagName<I>
+where
+	I: Iterator<Item = NodeDataRef<ElementData>>,
+{
+	/// The underlying iterator.
+	pub iter: I,
+
+	/// The name to match, or `None` to match every element.
+	name: Option<LocalName>,
+
+	/// The name to match against elements in the HTML namespace.
+	html_name: Option<LocalName>,
+}
+
+impl<I> ElementsByTagName<I>
+where
+	I: Iterator<Item = NodeDataRef<ElementData>>,
+{
+	fn new(iter: I, name: &str) -> Self {
+		if name == "*" {
+			return ElementsByTagName {
+				iter,
+				name: None,
+				html_name: None,
+			};
+		}
+		ElementsByTagName {
+			iter,
+			name: Some(LocalName::from(name)),
+			html_name: Some(LocalName::from(name.to_ascii_lowercase())),
+		}
+	}
+
+	fn matches(&self, element: &ElementData) -> bool {
+		let name = if element.name.ns == ns!(html) {
+			&self.html_name
+		} else {
+			&self.name
+		};
+		name.as_ref().is_none_or(|name| element.name.local == *name)
+	}
+}
+
+impl<I> Iterator for ElementsByTagName<I>
+where
+	I: Iterator<Item = NodeDataRef<ElementData>>,
+{
+	type Item = NodeDataRef<ElementData>;
+
+	#[inline]
+	fn next(&mut self) -> Option<NodeDataRef<ElementData>> {
+		while let Some(element) = self.iter.next() {
+			if self.matches(&element) {
+				return Some(element);
+			}
+		}
+		None
+	}
+}
+
+impl<I> DoubleEndedIterator for ElementsByTagName<I>
+where
+	I: DoubleEndedIterator<Item = NodeDataRef<ElementData>>,
+{
+	#[inline]
+	fn next_back(&mut self) -> Option<NodeDataRef<ElementData>> {
+		while let Some(element) = self.iter.next_back() {
+			if self.matches(&element) {
+				return Some(element);
+			}
+		}
+		None
+	}
+}
+
+/// An element iterator adaptor that yields elements with a given class.
+///
+/// See [`NodeRef::get_elements_by_class_name`].
+pub struct ElementsByClassName<I>
+where
+	I: Iterator<Item = NodeDataRef<ElementData>>,
+{
+	/// The underlying iterator.
+	pub iter: I,
+
+	/// The class to match.
+	class: Vec<u8>,
+
+	/// How to compare the class.
+	case_sensitivity: CaseSensitivity,
+}
+
+impl<I> ElementsByClassName<I>
+where
+	I: Iterator<Item = NodeDataRef<ElementData>>,
+{
+	#[inline]
+	fn matches(&self, element: &ElementData) -> bool {
+		element
+			.attributes
+			.borrow()
+			.has_class(&self.class, self.case_sensitivity)
+	}
+}
+
+impl<I> Iterator for ElementsByClassName<I>
+where
+	I: Iterator<Item = NodeDataRef<ElementData>>,
+{
+	type Item = NodeDataRef<ElementData>;
+
+	#[inline]
+	fn next(&mut self) -> Option<NodeDataRef<ElementData>> {
+		while let Some(element) = self.iter.next() {
+			if self.matches(&element) {
+				return Some(element);
+			}
+		}
+		None
+	}
+}
+
+impl<I> DoubleEndedIterator for ElementsByClassName<I>
+where
+	I: DoubleEndedIterator<Item = NodeDataRef<ElementData>>,
+{
+	#[inline]
+	fn next_back(&mut self) -> Option<NodeDataRef<ElementData>> {
+		while let Some(element) = self.iter.next_back() {
+			if self.matches(&element) {
+				return Some(element);
+			}
+		}
+		None
 	}
 }
 
@@ -447,19 +1131,32 @@ pub trait NodeIterator: Sized + Iterator<Item = NodeRef> {
 
 	/// Filter this node iterator to elements maching the given selectors.
 	#[inline]
-	fn select(self, selectors: &str) -> Result<Select<Elements<Self>>, ()> {
+	fn select(self, selectors: &str) -> Result<Select<Elements<Self>>, SelectorError> {
 		self.elements().select(selectors)
 	}
+
+	/// Return the first element of this node iterator maching the given selectors.
+	///
+	/// An invalid selector is an error, while a valid selector that matches nothing is `Ok(None)`.
+	#[inline]
+	fn select_first(
+		self,
+		selectors: &str,
+	) -> Result<Option<NodeDataRef<ElementData>>, SelectorError> {
+		Ok(self.select(selectors)?.next())
+	}
 }
 
 /// Convenience methods for element iterators.
 pub trait ElementIterator: Sized + Iterator<Item = NodeDataRef<ElementData>> {
 	/// Filter this element iterator to elements maching the given selectors.
 	#[inline]
-	fn select(self, selectors: &str) -> Result<Select<Self>, ()> {
+	fn select(self, selectors: &str) -> Result<Select<Self>, SelectorError> {
 		Selectors::compile(selectors).map(|s| Select {
 			iter: self,
 			selectors: s,
+			scope: None,
+			opts: MatchOpts::default(),
 		})
 	}
 }
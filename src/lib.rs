@@ -10,20 +10,39 @@ Kuchikiki (口利き) is an HTML tree manipulation library for Rust.
 extern crate html5ever;
 
 mod attributes;
+mod builder;
 mod cell_extras;
+mod encoding;
 pub mod iter;
 mod node_data_ref;
 mod parser;
+mod path;
 mod select;
 mod serializer;
 #[cfg(test)]
 mod tests;
+mod text;
 mod tree;
 
-pub use attributes::{Attribute, Attributes, ExpandedName};
+pub use attributes::{Attribute, Attributes, ClassList, ExpandedName};
+pub use builder::ElementBuilder;
+pub use html5ever::tree_builder::QuirksMode;
 pub use node_data_ref::NodeDataRef;
-pub use parser::{parse_fragment, parse_html, parse_html_with_options, ParseOpts, Sink};
-pub use select::{Selector, Selectors, Specificity};
+pub use parser::{
+	parse_fragment, parse_html, parse_html_from_bytes, parse_html_from_reader,
+	parse_html_with_limits, parse_html_with_options, parse_xml, parse_xml_with_options,
+	AdjacentPosition, FragmentError, FragmentParser, LimitError, LimitedSink, ParseLimits,
+	ParseOpts, Sink, XmlParseOpts,
+};
+pub use path::XPathError;
+pub use select::{
+	MatchOpts, SelectError, Selector, SelectorCache, SelectorError, Selectors, Specificity,
+};
+pub use selectors::attr::CaseSensitivity;
+pub use serializer::{
+	serialize_nodes, AttributeFilter, ElementAction, ElementFilter, HtmlSerializeOpts,
+	XmlSerializeOpts,
+};
 pub use tree::{Doctype, DocumentData, ElementData, Node, NodeData, NodeRef};
 
 /// This module re-exports a number of traits that are useful when using Kuchikiki.
@@ -22,6 +22,12 @@ impl NodeRef {
 		NodeDataRef::new_opt(self, Node::as_comment)
 	}
 
+	/// If this node is a processing instruction, return a strong reference to its target and data.
+	#[inline]
+	pub fn into_processing_instruction_ref(self) -> Option<NodeDataRef<RefCell<(String, String)>>> {
+		NodeDataRef::new_opt(self, Node::as_processing_instruction)
+	}
+
 	/// If this node is a doctype, return a strong reference to doctype-specific data.
 	#[inline]
 	pub fn into_doctype_ref(self) -> Option<NodeDataRef<Doctype>> {
@@ -68,10 +74,26 @@ impl<T> NodeDataRef<T> {
 	}
 
 	/// Access the corresponding node.
+	///
+	/// This is how to go from the elements yielded by `select`
+	/// to node methods for traversing or editing the tree, like `parent` or `detach`.
 	#[inline]
 	pub fn as_node(&self) -> &NodeRef {
 		&self._keep_alive
 	}
+
+	/// Convert into the corresponding node.
+	#[inline]
+	pub fn into_node(self) -> NodeRef {
+		self._keep_alive
+	}
+}
+
+impl<T> From<NodeDataRef<T>> for NodeRef {
+	#[inline]
+	fn from(data_ref: NodeDataRef<T>) -> NodeRef {
+		data_ref.into_node()
+	}
 }
 
 impl<T> Deref for NodeDataRef<T> {
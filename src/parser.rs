@@ -1,10 +1,18 @@
-use html5ever::tendril::StrTendril;
+use encoding_rs::Encoding;
+use html5ever::tendril::{StrTendril, TendrilSink};
 use html5ever::tree_builder::{ElementFlags, NodeOrText, QuirksMode, TreeSink};
 use html5ever::{self, Attribute, ExpandedName, QualName};
 use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Read};
+use std::rc::Rc;
 
 use crate::attributes;
-use crate::tree::NodeRef;
+use crate::encoding;
+use crate::tree::{NodeData, NodeRef};
 
 /// Options for the HTML parser.
 #[derive(Default)]
@@ -37,6 +45,138 @@ pub fn parse_html_with_options(opts: ParseOpts) -> html5ever::Parser<Sink> {
 	html5ever::parse_document(sink, html5opts)
 }
 
+/// Limits on the size of a document parsed with `parse_html_with_limits`.
+///
+/// `None` means no limit.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ParseLimits {
+	/// The maximum nesting depth of nodes, where the children of the document node are at depth 1.
+	///
+	/// Nodes in template contents count as nested in their template element.
+	pub max_depth: Option<usize>,
+
+	/// The maximum number of elements, comments and processing instructions.
+	///
+	/// Text nodes are not counted: the parser merges adjacent text,
+	/// so there are never many more text nodes than other nodes.
+	pub max_nodes: Option<usize>,
+}
+
+/// Error returned when a document exceeds its `ParseLimits`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LimitError {
+	/// Nodes are nested more deeply than `max_depth`.
+	TooDeep,
+	/// The document has more nodes than `max_nodes`.
+	TooManyNodes,
+}
+
+impl fmt::Display for LimitError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			LimitError::TooDeep => f.write_str("document exceeds the maximum nesting depth"),
+			LimitError::TooManyNodes => f.write_str("document exceeds the maximum number of nodes"),
+		}
+	}
+}
+
+impl Error for LimitError {}
+
+/// Parse an HTML document with html5ever with custom configuration,
+/// failing if the document exceeds the given limits.
+///
+/// Limits are checked as nodes are inserted, so a document exceeding them
+/// is never built in full, which guards against malicious or pathological input.
+pub fn parse_html_with_limits(
+	opts: ParseOpts,
+	limits: ParseLimits,
+) -> html5ever::Parser<LimitedSink> {
+	let sink = LimitedSink {
+		sink: Sink {
+			document_node: NodeRef::new_document(),
+			on_parse_error: opts.on_parse_error,
+		},
+		limits,
+		nodes: 0,
+		error: None,
+		depths: HashMap::new(),
+		template_depths: HashMap::new(),
+		placeholder_elements: HashMap::new(),
+		placeholder: None,
+	};
+	let html5opts = html5ever::ParseOpts {
+		tokenizer: opts.tokenizer,
+		tree_builder: opts.tree_builder,
+	};
+	html5ever::parse_document(sink, html5opts)
+}
+
+/// Parse a UTF-8 HTML document from a stream, with html5ever and the default configuration.
+///
+/// The input is fed to the tokenizer in chunks as it is read, rather than being buffered
+/// into one string first. Invalid UTF-8 sequences are replaced with U+FFFD.
+///
+/// This is the same as `parse_html().from_utf8().read_from(&mut reader)`.
+pub fn parse_html_from_reader<R: Read>(mut reader: R) -> io::Result<NodeRef> {
+	parse_html().from_utf8().read_from(&mut reader)
+}
+
+/// Parse an HTML document from bytes in a possibly non-UTF-8 character encoding,
+/// with html5ever and the default configuration.
+///
+/// The encoding is taken from a leading byte order mark if there is one,
+/// then from `encoding_override`, then from a `<meta>` charset declaration near the start
+/// of the document. Otherwise, the input is decoded as UTF-8 if it is valid UTF-8
+/// and as windows-1252 if not.
+///
+/// The encoding used is recorded on the document, and returned by `NodeRef::document_encoding`.
+pub fn parse_html_from_bytes(
+	bytes: &[u8],
+	encoding_override: Option<&'static Encoding>,
+) -> NodeRef {
+	let (encoding, bom_length) = encoding::sniff(bytes, encoding_override);
+	let (text, _) = encoding.decode_without_bom_handling(&bytes[bom_length..]);
+	let document = parse_html().one(&*text);
+	if let Some(data) = document.as_document() {
+		data._encoding.set(Some(encoding));
+	}
+	document
+}
+
+/// Options for the XML parser.
+#[derive(Default)]
+pub struct XmlParseOpts {
+	/// Options for the XML tokenizer.
+	pub tokenizer: xml5ever::tokenizer::XmlTokenizerOpts,
+
+	/// Options for the XML tree builder.
+	pub tree_builder: xml5ever::tree_builder::XmlTreeBuilderOpts,
+
+	/// A callback for XML parse errors (which are never fatal).
+	pub on_parse_error: Option<Box<dyn FnMut(Cow<'static, str>)>>,
+}
+
+/// Parse an XML document with xml5ever and the default configuration.
+///
+/// This produces the same kind of tree as `parse_html`,
+/// with namespaces and prefixes resolved by the XML parser.
+pub fn parse_xml() -> xml5ever::driver::XmlParser<Sink> {
+	parse_xml_with_options(XmlParseOpts::default())
+}
+
+/// Parse an XML document with xml5ever with custom configuration.
+pub fn parse_xml_with_options(opts: XmlParseOpts) -> xml5ever::driver::XmlParser<Sink> {
+	let sink = Sink {
+		document_node: NodeRef::new_document(),
+		on_parse_error: opts.on_parse_error,
+	};
+	let xml5opts = xml5ever::driver::XmlParseOpts {
+		tokenizer: opts.tokenizer,
+		tree_builder: opts.tree_builder,
+	};
+	xml5ever::driver::parse_document(sink, xml5opts)
+}
+
 /// Parse an HTML fragment with html5ever and the default configuration.
 pub fn parse_fragment(ctx_name: QualName, ctx_attr: Vec<Attribute>) -> html5ever::Parser<Sink> {
 	parse_fragment_with_options(ParseOpts::default(), ctx_name, ctx_attr)
@@ -59,6 +199,218 @@ pub fn parse_fragment_with_options(
 	html5ever::parse_fragment(sink, html5opts, ctx_name, ctx_attr)
 }
 
+/// A parser for many HTML fragments with the same context element and configuration.
+///
+/// This is a convenience, not an optimization: html5ever parsers can only be used once,
+/// so each call to `parse` creates a new one, like `parse_fragment` does.
+/// Only the context and options are kept between fragments.
+pub struct FragmentParser {
+	context_name: QualName,
+	context_attributes: Vec<Attribute>,
+	tokenizer: html5ever::tokenizer::TokenizerOpts,
+	tree_builder: html5ever::tree_builder::TreeBuilderOpts,
+	on_parse_error: Option<Rc<RefCell<ParseErrorCallback>>>,
+}
+
+type ParseErrorCallback = Box<dyn FnMut(Cow<'static, str>)>;
+
+impl FragmentParser {
+	/// Create a parser for fragments in the context of an element with the given name,
+	/// and the default configuration.
+	pub fn new(context_name: QualName) -> FragmentParser {
+		FragmentParser::with_options(ParseOpts::default(), context_name, vec![])
+	}
+
+	/// Create a parser for fragments in the context of an element with the given name
+	/// and attributes, and custom configuration.
+	///
+	/// The parse error callback, if any, is shared by all the fragments.
+	pub fn with_options(
+		opts: ParseOpts,
+		context_name: QualName,
+		context_attributes: Vec<Attribute>,
+	) -> FragmentParser {
+		FragmentParser {
+			context_name,
+			context_attributes,
+			tokenizer: opts.tokenizer,
+			tree_builder: opts.tree_builder,
+			on_parse_error: opts.on_parse_error.map(|f| Rc::new(RefCell::new(f))),
+		}
+	}
+
+	/// Parse an HTML fragment, returning a new document fragment node
+	/// whose children are the top-level parsed nodes.
+	///
+	/// This differs from `parse_fragment`, which returns a document node
+	/// with a single `<html>` element containing the parsed nodes.
+	/// Here that wrapper is removed, and the returned node has no document above it.
+	pub fn parse(&self, html: &str) -> NodeRef {
+		let on_parse_error = self
+			.on_parse_error
+			.clone()
+			.map(|f| Box::new(move |message| (f.borrow_mut())(message)) as ParseErrorCallback);
+		let opts = ParseOpts {
+			tokenizer: self.tokenizer.clone(),
+			tree_builder: self.tree_builder,
+			on_parse_error,
+		};
+		let document = parse_fragment_with_options(
+			opts,
+			self.context_name.clone(),
+			self.context_attributes.clone(),
+		)
+		.one(html);
+		let fragment = NodeRef::new(NodeData::DocumentFragment);
+		// The fragment parser wraps its output in a single `<html>` root element.
+		if let Some(root) = document.first_child() {
+			for child in root.children() {
+				fragment.append(child);
+			}
+		}
+		fragment
+	}
+}
+
+impl fmt::Debug for FragmentParser {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.debug_struct("FragmentParser")
+			.field("context_name", &self.context_name)
+			.field("context_attributes", &self.context_attributes)
+			.finish_non_exhaustive()
+	}
+}
+
+/// Error returned when an HTML fragment cannot be parsed in the context of a node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FragmentError {
+	/// The context node is not an element, so it cannot provide a parsing context.
+	NotAnElement,
+	/// The node has no parent, so nothing can be inserted next to it.
+	NoParent,
+}
+
+/// Where `NodeRef::insert_adjacent_html` inserts nodes relative to an element,
+/// matching the positions of the DOM `insertAdjacentHTML`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AdjacentPosition {
+	/// Before the element, as its previous siblings.
+	BeforeBegin,
+	/// Inside the element, before its first child.
+	AfterBegin,
+	/// Inside the element, after its last child.
+	BeforeEnd,
+	/// After the element, as its next siblings.
+	AfterEnd,
+}
+
+impl fmt::Display for FragmentError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			FragmentError::NotAnElement => f.write_str("fragment context node is not an element"),
+			FragmentError::NoParent => f.write_str("node has no parent"),
+		}
+	}
+}
+
+impl Error for FragmentError {}
+
+impl NodeRef {
+	/// Parse an HTML fragment using this element as the context,
+	/// returning the top-level parsed nodes.
+	///
+	/// The context matters: `<tr>` parses differently inside `<table>` than inside `<div>`.
+	fn parse_fragment_in_context(&self, html: &str) -> Result<Vec<NodeRef>, FragmentError> {
+		let element = self.as_element().ok_or(FragmentError::NotAnElement)?;
+		let document = parse_fragment(element.name.clone(), vec![]).one(html);
+		// The fragment parser wraps its output in a single `<html>` root element.
+		Ok(document
+			.first_child()
+			.map(|root| root.children().collect())
+			.unwrap_or_default())
+	}
+
+	/// Replace the children of this element with the result of parsing `html`
+	/// as a fragment in the context of this element, like the DOM `innerHTML` setter.
+	///
+	/// For `<template>` elements, this replaces the template contents.
+	pub fn set_inner_html(&self, html: &str) -> Result<(), FragmentError> {
+		let new_children = self.parse_fragment_in_context(html)?;
+		let parent = self.content_root();
+		parent.detach_children();
+		for child in new_children {
+			parent.append(child)
+		}
+		Ok(())
+	}
+
+	/// Parse `html` as a fragment in the context of this element
+	/// and append the resulting nodes after its existing children.
+	///
+	/// For `<template>` elements, the nodes are appended to the template contents.
+	pub fn append_html(&self, html: &str) -> Result<(), FragmentError> {
+		let new_children = self.parse_fragment_in_context(html)?;
+		let parent = self.content_root();
+		for child in new_children {
+			parent.append(child)
+		}
+		Ok(())
+	}
+
+	/// Parse `html` as a fragment in the context of this element
+	/// and insert the resulting nodes before its existing children.
+	///
+	/// For `<template>` elements, the nodes are prepended to the template contents.
+	pub fn prepend_html(&self, html: &str) -> Result<(), FragmentError> {
+		let new_children = self.parse_fragment_in_context(html)?;
+		let parent = self.content_root();
+		for child in new_children.into_iter().rev() {
+			parent.prepend(child)
+		}
+		Ok(())
+	}
+
+	/// Parse `html` as a fragment and insert the resulting nodes at the given position
+	/// relative to this element, like the DOM `insertAdjacentHTML`.
+	///
+	/// The fragment is parsed in the context of this element for the positions inside it,
+	/// and in the context of its parent for `BeforeBegin` and `AfterEnd`.
+	pub fn insert_adjacent_html(
+		&self,
+		position: AdjacentPosition,
+		html: &str,
+	) -> Result<(), FragmentError> {
+		match position {
+			AdjacentPosition::AfterBegin => self.prepend_html(html),
+			AdjacentPosition::BeforeEnd => self.append_html(html),
+			AdjacentPosition::BeforeBegin | AdjacentPosition::AfterEnd => {
+				self.as_element().ok_or(FragmentError::NotAnElement)?;
+				let parent = self.parent().ok_or(FragmentError::NoParent)?;
+				let new_siblings = parent.parse_fragment_in_context(html)?;
+				if position == AdjacentPosition::BeforeBegin {
+					for sibling in new_siblings {
+						self.insert_before(sibling)
+					}
+				} else {
+					for sibling in new_siblings.into_iter().rev() {
+						self.insert_after(sibling)
+					}
+				}
+				Ok(())
+			}
+		}
+	}
+
+	/// Return the node whose children are this element’s contents:
+	/// the template contents for `<template>` elements, and the element itself otherwise.
+	pub(crate) fn content_root(&self) -> NodeRef {
+		match self.as_element().and_then(|e| e.template_contents.as_ref()) {
+			Some(template_root) => template_root.clone(),
+			None => self.clone(),
+		}
+	}
+}
+
 /// Receives new tree nodes during parsing.
 pub struct Sink {
 	document_node: NodeRef,
@@ -140,6 +492,14 @@ impl TreeSink for Sink {
 
 	#[inline]
 	fn append(&mut self, parent: &NodeRef, child: NodeOrText<NodeRef>) {
+		// html5ever appends the children of `<template>` to its contents,
+		// but xml5ever appends them to the element itself.
+		if let Some(template_root) = parent
+			.as_element()
+			.and_then(|element| element.template_contents.as_ref())
+		{
+			return self.append(template_root, child);
+		}
 		match child {
 			NodeOrText::AppendNode(node) => parent.append(node),
 			NodeOrText::AppendText(text) => {
@@ -199,6 +559,7 @@ impl TreeSink for Sink {
 					attributes::Attribute { prefix, value }
 				});
 		}
+		attributes.update_class_cache();
 	}
 
 	#[inline]
@@ -243,3 +604,291 @@ impl TreeSink for Sink {
 		}
 	}
 }
+
+/// Receives new tree nodes during parsing, like `Sink`, while enforcing `ParseLimits`.
+///
+/// Once a limit is exceeded, the rest of the input is still tokenized
+/// but no more nodes are created or inserted, and parsing results in an error.
+pub struct LimitedSink {
+	sink: Sink,
+	limits: ParseLimits,
+	nodes: usize,
+	error: Option<LimitError>,
+	/// The depth of each inserted node, when there is a `max_depth`.
+	depths: HashMap<NodeRef, usize>,
+	/// The depth of the template element owning each template contents fragment.
+	template_depths: HashMap<NodeRef, usize>,
+	/// The nodes returned instead of new ones once a limit is exceeded:
+	/// one element per name, as the tree builder looks at the names of open elements,
+	/// and one comment for comments and processing instructions.
+	placeholder_elements: HashMap<QualName, NodeRef>,
+	placeholder: Option<NodeRef>,
+}
+
+impl LimitedSink {
+	/// Return the depth of a node, from the depth recorded for it or its nearest ancestor.
+	fn depth(&self, node: &NodeRef) -> usize {
+		let mut above = 0;
+		let mut current = node.clone();
+		loop {
+			if let Some(&depth) = self.depths.get(&current) {
+				return depth + above;
+			}
+			if let Some(&depth) = self.template_depths.get(&current) {
+				return depth + above;
+			}
+			match current.parent() {
+				Some(parent) => current = parent,
+				None => return above,
+			}
+			above += 1;
+		}
+	}
+
+	/// Record the depths of a node just inserted at the given depth and of its descendants,
+	/// including those in template contents, and return whether they are within `max_depth`.
+	///
+	/// New nodes have no descendants, so this only walks subtrees that the tree builder moves.
+	fn record_depths(&mut self, node: &NodeRef, depth: usize, max_depth: usize) -> bool {
+		let mut deepest = depth;
+		let mut stack = vec![(node.clone(), depth)];
+		while let Some((node, depth)) = stack.pop() {
+			deepest = deepest.max(depth);
+			stack.extend(node.children().map(|child| (child, depth + 1)));
+			if let Some(contents) = node.as_element().and_then(|e| e.template_contents.as_ref()) {
+				self.template_depths.insert(contents.clone(), depth);
+				stack.extend(contents.children().map(|child| (child, depth + 1)));
+			}
+			self.depths.insert(node, depth);
+		}
+		self.check_depth(deepest, max_depth)
+	}
+
+	/// Return whether a depth is within `max_depth`, recording an error if not.
+	fn check_depth(&mut self, depth: usize, max_depth: usize) -> bool {
+		if depth > max_depth {
+			self.error = Some(LimitError::TooDeep);
+			return false;
+		}
+		true
+	}
+
+	/// Insert a child at the given depth with `insert`, checking the depth afterwards.
+	///
+	/// Depths are only computed when there is a `max_depth`.
+	fn insert<D, I>(&mut self, child: NodeOrText<NodeRef>, depth: D, insert: I)
+	where
+		D: FnOnce(&Self) -> usize,
+		I: FnOnce(&mut Sink, NodeOrText<NodeRef>),
+	{
+		if self.error.is_some() {
+			return;
+		}
+		let max_depth = match self.limits.max_depth {
+			Some(max_depth) => max_depth,
+			None => return insert(&mut self.sink, child),
+		};
+		let depth = depth(self);
+		match child {
+			NodeOrText::AppendNode(node) => {
+				insert(&mut self.sink, NodeOrText::AppendNode(node.clone()));
+				self.record_depths(&node, depth, max_depth);
+			}
+			NodeOrText::AppendText(text) => {
+				if self.check_depth(depth, max_depth) {
+					insert(&mut self.sink, NodeOrText::AppendText(text))
+				}
+			}
+		}
+	}
+
+	/// Count a new node, returning whether it can be created.
+	fn count_node(&mut self) -> bool {
+		if self.error.is_some() {
+			return false;
+		}
+		self.nodes += 1;
+		if self.limits.max_nodes.is_some_and(|max| self.nodes > max) {
+			self.error = Some(LimitError::TooManyNodes);
+			return false;
+		}
+		true
+	}
+
+	fn placeholder(&mut self) -> NodeRef {
+		self.placeholder
+			.get_or_insert_with(|| NodeRef::new_comment(""))
+			.clone()
+	}
+}
+
+impl TreeSink for LimitedSink {
+	type Output = Result<NodeRef, LimitError>;
+
+	fn finish(self) -> Result<NodeRef, LimitError> {
+		match self.error {
+			Some(error) => Err(error),
+			None => Ok(self.sink.finish()),
+		}
+	}
+
+	type Handle = NodeRef;
+
+	#[inline]
+	fn parse_error(&mut self, message: Cow<'static, str>) {
+		self.sink.parse_error(message)
+	}
+
+	#[inline]
+	fn get_document(&mut self) -> NodeRef {
+		self.sink.get_document()
+	}
+
+	#[inline]
+	fn set_quirks_mode(&mut self, mode: QuirksMode) {
+		self.sink.set_quirks_mode(mode)
+	}
+
+	#[inline]
+	fn same_node(&self, x: &NodeRef, y: &NodeRef) -> bool {
+		self.sink.same_node(x, y)
+	}
+
+	#[inline]
+	fn elem_name<'a>(&self, target: &'a NodeRef) -> ExpandedName<'a> {
+		target.as_element().unwrap().name.expanded()
+	}
+
+	#[inline]
+	fn create_element(
+		&mut self,
+		name: QualName,
+		attrs: Vec<Attribute>,
+		flags: ElementFlags,
+	) -> NodeRef {
+		if self.count_node() {
+			return self.sink.create_element(name, attrs, flags);
+		}
+		if let Some(placeholder) = self.placeholder_elements.get(&name) {
+			return placeholder.clone();
+		}
+		let placeholder = self.sink.create_element(name.clone(), vec![], flags);
+		self.placeholder_elements.insert(name, placeholder.clone());
+		placeholder
+	}
+
+	#[inline]
+	fn create_comment(&mut self, text: StrTendril) -> NodeRef {
+		if self.count_node() {
+			self.sink.create_comment(text)
+		} else {
+			self.placeholder()
+		}
+	}
+
+	#[inline]
+	fn create_pi(&mut self, target: StrTendril, data: StrTendril) -> NodeRef {
+		if self.count_node() {
+			self.sink.create_pi(target, data)
+		} else {
+			self.placeholder()
+		}
+	}
+
+	#[inline]
+	fn append(&mut self, parent: &NodeRef, child: NodeOrText<NodeRef>) {
+		self.insert(
+			child,
+			|this| this.depth(parent) + 1,
+			|sink, child| sink.append(parent, child),
+		)
+	}
+
+	#[inline]
+	fn append_before_sibling(&mut self, sibling: &NodeRef, child: NodeOrText<NodeRef>) {
+		self.insert(
+			child,
+			|this| this.depth(sibling),
+			|sink, child| sink.append_before_sibling(sibling, child),
+		)
+	}
+
+	#[inline]
+	fn append_doctype_to_document(
+		&mut self,
+		name: StrTendril,
+		public_id: StrTendril,
+		system_id: StrTendril,
+	) {
+		if self.error.is_some() {
+			return;
+		}
+		if let Some(max_depth) = self.limits.max_depth {
+			if !self.check_depth(1, max_depth) {
+				return;
+			}
+		}
+		self.sink
+			.append_doctype_to_document(name, public_id, system_id)
+	}
+
+	#[inline]
+	fn add_attrs_if_missing(&mut self, target: &NodeRef, attrs: Vec<Attribute>) {
+		if self.error.is_none() {
+			self.sink.add_attrs_if_missing(target, attrs)
+		}
+	}
+
+	#[inline]
+	fn remove_from_parent(&mut self, target: &NodeRef) {
+		self.sink.remove_from_parent(target)
+	}
+
+	#[inline]
+	fn reparent_children(&mut self, node: &NodeRef, new_parent: &NodeRef) {
+		if self.error.is_some() {
+			return;
+		}
+		let first_moved = node.first_child();
+		self.sink.reparent_children(node, new_parent);
+		if let Some(max_depth) = self.limits.max_depth {
+			let depth = self.depth(new_parent) + 1;
+			let moved = first_moved
+				.into_iter()
+				.flat_map(|first| first.inclusive_following_siblings());
+			for child in moved.collect::<Vec<_>>() {
+				if !self.record_depths(&child, depth, max_depth) {
+					break;
+				}
+			}
+		}
+	}
+
+	#[inline]
+	fn mark_script_already_started(&mut self, node: &NodeRef) {
+		self.sink.mark_script_already_started(node)
+	}
+
+	#[inline]
+	fn get_template_contents(&mut self, target: &NodeRef) -> NodeRef {
+		let contents = self.sink.get_template_contents(target);
+		if self.limits.max_depth.is_some() {
+			let depth = self.depth(target);
+			self.template_depths.insert(contents.clone(), depth);
+		}
+		contents
+	}
+
+	fn append_based_on_parent_node(
+		&mut self,
+		element: &NodeRef,
+		prev_element: &NodeRef,
+		child: NodeOrText<NodeRef>,
+	) {
+		if element.parent().is_some() {
+			self.append_before_sibling(element, child)
+		} else {
+			self.append(prev_element, child)
+		}
+	}
+}
@@ -0,0 +1,272 @@
+use cssparser::serialize_identifier;
+use std::error::Error;
+use std::fmt::{self, Write};
+
+use crate::iter::NodeIterator;
+use crate::tree::{NodeData, NodeRef};
+
+impl NodeRef {
+	/// Return a CSS selector that matches this element and nothing else in its tree,
+	/// such as `#main > div:nth-of-type(2) > p`, or `None` if this node is not an element.
+	///
+	/// The path goes up from this element to the nearest ancestor with an `id`
+	/// that is unique in the tree, or to the document’s root element, written as `:root`.
+	/// Each other step is the element’s name, with `:nth-of-type` when it has siblings of the same type.
+	///
+	/// Outside a document, such as in template contents, the path starts at the topmost element
+	/// and can also match elements below it that have the same ancestry.
+	pub fn css_path(&self) -> Option<String> {
+		self.as_element()?;
+		let root = self.inclusive_ancestors().last()?;
+		let mut steps = Vec::new();
+		for node in self.inclusive_ancestors() {
+			let element = match node.as_element() {
+				Some(element) => element,
+				None => break,
+			};
+			if let Some(id) = element.id().filter(|id| is_unique_id(&root, id)) {
+				steps.push(format!("#{}", identifier(&id)));
+				break;
+			}
+			if node
+				.parent()
+				.is_some_and(|parent| matches!(*parent.data(), NodeData::Document(_)))
+			{
+				steps.push(":root".to_owned());
+				break;
+			}
+			let same_type = |sibling: &NodeRef| {
+				sibling.as_element().is_some_and(|sibling| {
+					sibling.name.local == element.name.local && sibling.name.ns == element.name.ns
+				})
+			};
+			let mut step = identifier(&element.name.local);
+			let preceding = node.preceding_siblings().filter(same_type).count();
+			if preceding > 0 || node.following_siblings().any(|sibling| same_type(&sibling)) {
+				write!(step, ":nth-of-type({})", preceding + 1).unwrap();
+			}
+			steps.push(step);
+		}
+		steps.reverse();
+		Some(steps.join(" > "))
+	}
+
+	/// Return an absolute XPath that locates this node, such as `/html/body/div[2]/p`.
+	///
+	/// Elements are named by their local name, other nodes by a node test such as `text()`.
+	/// A step has a position, starting at 1, when the node has siblings that pass the same test.
+	/// The path starts at the root of the tree, which for a document or document fragment is `/`.
+	pub fn xpath(&self) -> String {
+		let mut steps = Vec::new();
+		for node in self.inclusive_ancestors() {
+			let test = match NodeTest::of(&node) {
+				Some(test) => test,
+				None => break,
+			};
+			let mut step = test.to_string();
+			if node.parent().is_some() {
+				let preceding = node
+					.preceding_siblings()
+					.filter(|s| test.matches(s))
+					.count();
+				if preceding > 0 || node.following_siblings().any(|s| test.matches(&s)) {
+					write!(step, "[{}]", preceding + 1).unwrap();
+				}
+			}
+			steps.push(step);
+		}
+		steps.reverse();
+		format!("/{}", steps.join("/"))
+	}
+
+	/// Return the nodes that an XPath selects, in document order.
+	///
+	/// Only paths of child steps are supported, such as `/html/body/div[2]/p` as made by `xpath`.
+	/// Each step is an element name, `*`, `text()`, `comment()`, `processing-instruction()`
+	/// or `node()`, optionally followed by a position such as `[2]`.
+	/// Element names are compared to local names, ignoring namespaces,
+	/// and ASCII case-insensitively for HTML elements.
+	///
+	/// A path starting with `/` is absolute: it starts at the root of this node’s tree.
+	/// Other paths start at this node.
+	pub fn evaluate_xpath(&self, path: &str) -> Result<Vec<NodeRef>, XPathError> {
+		let (root, steps) = match path.strip_prefix('/') {
+			Some(steps) => (self.inclusive_ancestors().last().unwrap(), steps),
+			None => (self.clone(), path),
+		};
+		if steps.is_empty() {
+			return Ok(if path.is_empty() {
+				Vec::new()
+			} else {
+				vec![root]
+			});
+		}
+		let steps = steps
+			.split('/')
+			.map(Step::parse)
+			.collect::<Result<Vec<_>, _>>()?;
+
+		// The candidates of each step are grouped by parent, as positions count among siblings.
+		// A root without a document or fragment above it is the only candidate for the first step.
+		let mut groups = if path.starts_with('/') && NodeTest::of(&root).is_some() {
+			vec![vec![root]]
+		} else {
+			vec![root.children().collect()]
+		};
+		let mut selected = Vec::new();
+		for (i, step) in steps.iter().enumerate() {
+			selected = groups
+				.iter()
+				.flat_map(|candidates| step.select(candidates))
+				.collect::<Vec<_>>();
+			if i + 1 < steps.len() {
+				groups = selected
+					.iter()
+					.map(|node| node.children().collect())
+					.collect();
+			}
+		}
+		Ok(selected)
+	}
+}
+
+/// Return whether exactly one element in the tree of `root` has the given non-empty `id`.
+fn is_unique_id(root: &NodeRef, id: &str) -> bool {
+	!id.is_empty()
+		&& root
+			.inclusive_descendants()
+			.elements()
+			.filter(|element| element.attributes.borrow().get(local_name!("id")) == Some(id))
+			.nth(1)
+			.is_none()
+}
+
+/// Escape `name` as a CSS identifier.
+fn identifier(name: &str) -> String {
+	let mut escaped = String::new();
+	serialize_identifier(name, &mut escaped).unwrap();
+	escaped
+}
+
+/// An error returned by `NodeRef::evaluate_xpath` for a path outside the supported subset.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct XPathError {
+	/// The step of the path that is not supported, such as `div[@id]`.
+	pub step: String,
+}
+
+impl fmt::Display for XPathError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "unsupported XPath step `{}`", self.step)
+	}
+}
+
+impl Error for XPathError {}
+
+/// One step of an XPath on the child axis, such as `div[2]`.
+struct Step {
+	test: NodeTest,
+	/// The position among the siblings that pass the test, starting at 1.
+	position: Option<usize>,
+}
+
+impl Step {
+	fn parse(step: &str) -> Result<Step, XPathError> {
+		let error = || XPathError {
+			step: step.to_owned(),
+		};
+		let (test, position) = match step.strip_suffix(']') {
+			Some(rest) => {
+				let (test, position) = rest.split_once('[').ok_or_else(error)?;
+				let position = position.parse().ok().filter(|&n| n > 0).ok_or_else(error)?;
+				(test, Some(position))
+			}
+			None => (step, None),
+		};
+		let test = match test {
+			"*" => NodeTest::Element,
+			"text()" => NodeTest::Text,
+			"comment()" => NodeTest::Comment,
+			"processing-instruction()" => NodeTest::ProcessingInstruction,
+			"node()" => NodeTest::Node,
+			name if is_name(name) => NodeTest::Name(name.to_owned()),
+			_ => return Err(error()),
+		};
+		Ok(Step { test, position })
+	}
+
+	fn select(&self, candidates: &[NodeRef]) -> Vec<NodeRef> {
+		let mut matches = candidates.iter().filter(|node| self.test.matches(node));
+		match self.position {
+			Some(position) => matches.nth(position - 1).into_iter().cloned().collect(),
+			None => matches.cloned().collect(),
+		}
+	}
+}
+
+/// The kinds of nodes that an XPath step selects.
+enum NodeTest {
+	/// Elements with the given local name.
+	Name(String),
+	/// Any element: `*`.
+	Element,
+	/// `text()`
+	Text,
+	/// `comment()`
+	Comment,
+	/// `processing-instruction()`
+	ProcessingInstruction,
+	/// Any node: `node()`.
+	Node,
+}
+
+impl NodeTest {
+	/// Return the most specific test that `node` passes,
+	/// or `None` for documents and document fragments, which are only ever roots.
+	fn of(node: &NodeRef) -> Option<NodeTest> {
+		Some(match node.data() {
+			NodeData::Element(element) => NodeTest::Name(element.name.local.to_string()),
+			NodeData::Text(_) => NodeTest::Text,
+			NodeData::Comment(_) => NodeTest::Comment,
+			NodeData::ProcessingInstruction(_) => NodeTest::ProcessingInstruction,
+			NodeData::Doctype(_) => NodeTest::Node,
+			NodeData::Document(_) | NodeData::DocumentFragment => return None,
+		})
+	}
+
+	fn matches(&self, node: &NodeRef) -> bool {
+		match (self, node.data()) {
+			(NodeTest::Name(name), NodeData::Element(element)) => {
+				let local = &*element.name.local;
+				local == name || (element.name.ns == ns!(html) && local.eq_ignore_ascii_case(name))
+			}
+			(NodeTest::Element, NodeData::Element(_))
+			| (NodeTest::Text, NodeData::Text(_))
+			| (NodeTest::Comment, NodeData::Comment(_))
+			| (NodeTest::ProcessingInstruction, NodeData::ProcessingInstruction(_))
+			| (NodeTest::Node, _) => true,
+			_ => false,
+		}
+	}
+}
+
+impl fmt::Display for NodeTest {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.write_str(match self {
+			NodeTest::Name(name) => name,
+			NodeTest::Element => "*",
+			NodeTest::Text => "text()",
+			NodeTest::Comment => "comment()",
+			NodeTest::ProcessingInstruction => "processing-instruction()",
+			NodeTest::Node => "node()",
+		})
+	}
+}
+
+/// Return whether `name` can be written as an XPath name test:
+/// a letter or `_` followed by letters, digits, `-`, `.` and `_`.
+fn is_name(name: &str) -> bool {
+	let mut chars = name.chars();
+	chars.next().is_some_and(|c| c.is_alphabetic() || c == '_')
+		&& chars.all(|c| c.is_alphanumeric() || matches!(c, '-' | '.' | '_'))
+}
@@ -1,21 +1,24 @@
-// Addressing this lint is a semver-breaking change.
-// Remove this once the issue has been addressed.
-#![allow(clippy::result_unit_err)]
-
 use crate::attributes::ExpandedName;
 use crate::iter::{NodeIterator, Select};
 use crate::node_data_ref::NodeDataRef;
 use crate::tree::{ElementData, Node, NodeData, NodeRef};
-use cssparser::{self, CowRcStr, ParseError, SourceLocation, ToCss};
+use cssparser::{
+	self, BasicParseErrorKind, CowRcStr, ParseError, ParseErrorKind, SourceLocation, ToCss, Token,
+};
 use html5ever::{LocalName, Namespace};
 use selectors::attr::{AttrSelectorOperation, CaseSensitivity, NamespaceConstraint};
 use selectors::context::QuirksMode;
 use selectors::parser::SelectorParseErrorKind;
 use selectors::parser::{
-	NonTSPseudoClass, Parser, Selector as GenericSelector, SelectorImpl, SelectorList,
+	Component, NonTSPseudoClass, Parser, Selector as GenericSelector, SelectorImpl, SelectorList,
 };
 use selectors::{self, matching, OpaqueElement};
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::error::Error;
 use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
 
 #[derive(Debug, Clone)]
 pub struct KuchikiSelectors;
@@ -77,6 +80,125 @@ impl<'i> Parser<'i> for KuchikiParser {
 			)
 		}
 	}
+
+	fn parse_non_ts_functional_pseudo_class<'t>(
+		&self,
+		name: CowRcStr<'i>,
+		arguments: &mut cssparser::Parser<'i, 't>,
+	) -> Result<PseudoClass, ParseError<'i, SelectorParseErrorKind<'i>>> {
+		if name.eq_ignore_ascii_case("has") {
+			let selectors = arguments.parse_comma_separated(|input| self.parse_relative(input))?;
+			Ok(PseudoClass::Has(RelativeSelectors::new(selectors)))
+		} else {
+			Err(arguments.new_custom_error(
+				SelectorParseErrorKind::UnsupportedPseudoClassOrElement(name),
+			))
+		}
+	}
+}
+
+impl KuchikiParser {
+	/// Parse a selector relative to an anchor element, which may start with a combinator,
+	/// and compile it with a leading `:scope` standing for the anchor.
+	///
+	/// Returns whether the selector can reach siblings of the anchor, with `+` or `~`.
+	fn parse_relative<'i>(
+		&self,
+		input: &mut cssparser::Parser<'i, '_>,
+	) -> Result<(bool, Selector), ParseError<'i, SelectorParseErrorKind<'i>>> {
+		let start = input.position();
+		let reaches_siblings = input
+			.try_parse(|input| match input.next() {
+				Ok(Token::Delim('+')) | Ok(Token::Delim('~')) => Ok(true),
+				Ok(Token::Delim('>')) => Ok(false),
+				_ => Err(()),
+			})
+			.unwrap_or(false);
+		// Parse in place first, for errors located in the original input.
+		GenericSelector::parse(self, input)?;
+		let scoped = format!(":scope {}", input.slice_from(start));
+		match Selectors::compile(&scoped) {
+			Ok(Selectors(mut list)) if list.len() == 1 => Ok((reaches_siblings, list.remove(0))),
+			_ => Err(input.new_custom_error(SelectorParseErrorKind::InvalidState)),
+		}
+	}
+}
+
+/// The argument of `:has()`.
+#[derive(Clone)]
+pub struct RelativeSelectors {
+	/// The serialization of the selectors, without the leading `:scope`,
+	/// which is also what they are compared and hashed by.
+	css: String,
+	/// The specificity of the most specific selector, not counting the leading `:scope`.
+	specificity: u32,
+	selectors: Arc<[(bool, Selector)]>,
+}
+
+impl RelativeSelectors {
+	fn new(selectors: Vec<(bool, Selector)>) -> RelativeSelectors {
+		let css = selectors
+			.iter()
+			.map(|(_, selector)| {
+				let scoped = selector.to_string();
+				scoped
+					.strip_prefix(":scope")
+					.unwrap_or(&scoped)
+					.trim_start()
+					.to_owned()
+			})
+			.collect::<Vec<_>>()
+			.join(", ");
+		let specificity = selectors
+			.iter()
+			.map(|(_, selector)| selector.specificity().0 - SCOPE_SPECIFICITY)
+			.max()
+			.unwrap_or(0);
+		RelativeSelectors {
+			css,
+			specificity,
+			selectors: selectors.into(),
+		}
+	}
+
+	/// Returns whether an element after the anchor, in tree order and under the same parent,
+	/// matches one of these selectors.
+	fn matches_relative_to(&self, anchor: &NodeRef, opts: &MatchOpts) -> bool {
+		self.selectors.iter().any(|(reaches_siblings, selector)| {
+			let matches = |element: NodeDataRef<ElementData>| {
+				selector.matches_with_opts(&element, Some(anchor), opts)
+			};
+			if *reaches_siblings {
+				anchor
+					.following_siblings()
+					.flat_map(|sibling| sibling.inclusive_descendants())
+					.elements()
+					.any(matches)
+			} else {
+				anchor.descendants().elements().any(matches)
+			}
+		})
+	}
+}
+
+impl PartialEq for RelativeSelectors {
+	fn eq(&self, other: &Self) -> bool {
+		self.css == other.css
+	}
+}
+
+impl Eq for RelativeSelectors {}
+
+impl Hash for RelativeSelectors {
+	fn hash<H: Hasher>(&self, state: &mut H) {
+		self.css.hash(state)
+	}
+}
+
+impl fmt::Debug for RelativeSelectors {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.debug_tuple("RelativeSelectors").field(&self.css).finish()
+	}
 }
 
 #[derive(PartialEq, Eq, Clone, Debug, Hash)]
@@ -91,6 +213,7 @@ pub enum PseudoClass {
 	Disabled,
 	Checked,
 	Indeterminate,
+	Has(RelativeSelectors),
 }
 
 impl NonTSPseudoClass for PseudoClass {
@@ -108,7 +231,8 @@ impl NonTSPseudoClass for PseudoClass {
 	}
 
 	fn has_zero_specificity(&self) -> bool {
-		false
+		// `Selector::specificity` counts `:has()` as its most specific argument instead.
+		matches!(*self, PseudoClass::Has(_))
 	}
 }
 
@@ -118,6 +242,9 @@ impl ToCss for PseudoClass {
 		W: fmt::Write,
 	{
 		dest.write_str(match *self {
+			PseudoClass::Has(ref relative) => {
+				return write!(dest, ":has({})", relative.css);
+			}
 			PseudoClass::AnyLink => ":any-link",
 			PseudoClass::Link => ":link",
 			PseudoClass::Visited => ":visited",
@@ -256,9 +383,7 @@ impl selectors::Element for NodeDataRef<ElementData> {
 		self.attributes
 			.borrow()
 			.get(local_name!("id"))
-			.map_or(false, |id_attr| {
-				case_sensitivity.eq(id.as_bytes(), id_attr.as_bytes())
-			})
+			.is_some_and(|id_attr| case_sensitivity.eq(id.as_bytes(), id_attr.as_bytes()))
 	}
 
 	#[inline]
@@ -274,6 +399,22 @@ impl selectors::Element for NodeDataRef<ElementData> {
 		local_name: &LocalName,
 		operation: &AttrSelectorOperation<&String>,
 	) -> bool {
+		let forced;
+		let operation = match *operation {
+			AttrSelectorOperation::WithValue {
+				operator,
+				expected_value,
+				..
+			} if ATTR_CASE_SENSITIVITY.with(Cell::get) == CaseSensitivity::AsciiCaseInsensitive => {
+				forced = AttrSelectorOperation::WithValue {
+					operator,
+					case_sensitivity: CaseSensitivity::AsciiCaseInsensitive,
+					expected_value,
+				};
+				&forced
+			}
+			_ => operation,
+		};
 		let attrs = self.attributes.borrow();
 		match *ns {
 			NamespaceConstraint::Any => attrs
@@ -283,7 +424,7 @@ impl selectors::Element for NodeDataRef<ElementData> {
 			NamespaceConstraint::Specific(ns_url) => attrs
 				.map
 				.get(&ExpandedName::new(ns_url, local_name.clone()))
-				.map_or(false, |attr| operation.eval_str(&attr.value)),
+				.is_some_and(|attr| operation.eval_str(&attr.value)),
 		}
 	}
 
@@ -298,7 +439,7 @@ impl selectors::Element for NodeDataRef<ElementData> {
 	fn match_non_ts_pseudo_class<F>(
 		&self,
 		pseudo: &PseudoClass,
-		_context: &mut matching::MatchingContext<KuchikiSelectors>,
+		context: &mut matching::MatchingContext<KuchikiSelectors>,
 		_flags_setter: &mut F,
 	) -> bool
 	where
@@ -309,6 +450,12 @@ impl selectors::Element for NodeDataRef<ElementData> {
 			Active | Focus | Hover | Enabled | Disabled | Checked | Indeterminate | Visited => {
 				false
 			}
+			Has(ref relative) => {
+				let opts = MatchOpts {
+					case_sensitivity: context.classes_and_ids_case_sensitivity(),
+				};
+				relative.matches_relative_to(self.as_node(), &opts)
+			}
 			AnyLink | Link => {
 				self.name.ns == ns!(html)
 					&& matches!(
@@ -320,7 +467,59 @@ impl selectors::Element for NodeDataRef<ElementData> {
 	}
 }
 
+thread_local! {
+	/// The case sensitivity of `MatchOpts` for the selector being matched on this thread,
+	/// since `selectors` gives no matching context to `attr_matches`.
+	static ATTR_CASE_SENSITIVITY: Cell<CaseSensitivity> =
+		const { Cell::new(CaseSensitivity::CaseSensitive) };
+}
+
+/// Options for matching selectors against elements.
+///
+/// Create them with `MatchOpts::new()` and set each option with its method,
+/// as in `MatchOpts::new().case_sensitivity(CaseSensitivity::AsciiCaseInsensitive)`,
+/// since more options may be added.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct MatchOpts {
+	/// How class, ID and attribute selectors are compared with attribute values.
+	///
+	/// This does not depend on the quirks mode of the document, and defaults to case-sensitive.
+	/// When case-sensitive, attribute selectors are compared as they say,
+	/// which is case-insensitively with the `i` flag, as in `[type=text i]`.
+	/// When ASCII case-insensitive, all of them are compared case-insensitively.
+	pub case_sensitivity: CaseSensitivity,
+}
+
+impl MatchOpts {
+	/// Return the default options.
+	pub fn new() -> MatchOpts {
+		MatchOpts::default()
+	}
+
+	/// Set how class, ID and attribute selectors are compared.
+	pub fn case_sensitivity(mut self, case_sensitivity: CaseSensitivity) -> Self {
+		self.case_sensitivity = case_sensitivity;
+		self
+	}
+}
+
+impl Default for MatchOpts {
+	fn default() -> Self {
+		MatchOpts {
+			case_sensitivity: CaseSensitivity::CaseSensitive,
+		}
+	}
+}
+
 /// A pre-compiled list of CSS Selectors.
+///
+/// Selectors parsed by another CSS library, such as the rules of a lightningcss stylesheet,
+/// cannot be used directly: lightningcss uses its own fork of the `selectors` crate,
+/// whose types are unrelated to the ones here. Serialize them back to CSS
+/// and compile each one once with `Selectors::compile`, then match with
+/// `NodeRef::select_compiled` for every document, instead of calling `select` with the string.
+/// Compiled selectors can also be combined into one list, as `Selectors(vec![...])`.
 pub struct Selectors(pub Vec<Selector>);
 
 /// A pre-compiled CSS Selector.
@@ -332,24 +531,179 @@ pub struct Selector(GenericSelector<KuchikiSelectors>);
 ///
 /// Determines precedence in the cascading algorithm.
 /// When equal, a rule later in source order takes precedence.
-#[derive(Copy, Clone, Hash, Eq, PartialEq, Ord, PartialOrd)]
+#[derive(Copy, Clone, Debug, Hash, Eq, PartialEq, Ord, PartialOrd)]
 pub struct Specificity(u32);
 
+/// An error that occurred while compiling a list of selectors.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SelectorError {
+	/// The line of the selector string where the error occurred, starting at 1.
+	pub line: u32,
+
+	/// The column of the selector string where the error occurred, starting at 1.
+	pub column: u32,
+
+	/// A description of what went wrong.
+	pub message: String,
+}
+
+impl SelectorError {
+	fn new(error: ParseError<SelectorParseErrorKind>) -> SelectorError {
+		use self::SelectorParseErrorKind::*;
+		let message = match error.kind {
+			ParseErrorKind::Basic(BasicParseErrorKind::UnexpectedToken(token)) => {
+				format!("unexpected token `{}`", token.to_css_string())
+			}
+			ParseErrorKind::Basic(BasicParseErrorKind::EndOfInput) => {
+				"unexpected end of input".to_owned()
+			}
+			ParseErrorKind::Basic(_) => "invalid selector".to_owned(),
+			ParseErrorKind::Custom(kind) => match kind {
+				PseudoElementInComplexSelector => "pseudo-element in complex selector".to_owned(),
+				EmptySelector => "empty selector".to_owned(),
+				DanglingCombinator => "combinator not followed by a selector".to_owned(),
+				NonSimpleSelectorInNegation => "non-simple selector in :not()".to_owned(),
+				NonCompoundSelector => "expected a compound selector".to_owned(),
+				EmptyNegation => "empty :not()".to_owned(),
+				UnsupportedPseudoClassOrElement(name) => {
+					format!("unsupported pseudo-class or pseudo-element `{}`", name)
+				}
+				UnexpectedIdent(name) => format!("unexpected identifier `{}`", name),
+				ExpectedNamespace(prefix) => format!("unknown namespace prefix `{}`", prefix),
+				NoQualifiedNameInAttributeSelector(token)
+				| UnexpectedTokenInAttributeSelector(token)
+				| ExpectedBarInAttr(token)
+				| BadValueInAttr(token)
+				| InvalidQualNameInAttr(token) => format!(
+					"unexpected token `{}` in attribute selector",
+					token.to_css_string()
+				),
+				PseudoElementExpectedColon(token)
+				| PseudoElementExpectedIdent(token)
+				| NoIdentForPseudo(token) => format!(
+					"unexpected token `{}` in pseudo-class or pseudo-element",
+					token.to_css_string()
+				),
+				ExplicitNamespaceUnexpectedToken(token) => format!(
+					"unexpected token `{}` after namespace",
+					token.to_css_string()
+				),
+				ClassNeedsIdent(token) => {
+					format!("expected a class name, found `{}`", token.to_css_string())
+				}
+				NonPseudoElementAfterSlotted | InvalidPseudoElementAfterSlotted | InvalidState => {
+					"invalid selector".to_owned()
+				}
+			},
+		};
+		SelectorError {
+			line: error.location.line + 1,
+			column: error.location.column,
+			message,
+		}
+	}
+}
+
+impl fmt::Display for SelectorError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(
+			f,
+			"{} at line {}, column {}",
+			self.message, self.line, self.column
+		)
+	}
+}
+
+impl Error for SelectorError {}
+
+/// An error returned by `NodeRef::select_one` when there is not exactly one matching element.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SelectError {
+	/// The selector string could not be compiled.
+	InvalidSelector(SelectorError),
+
+	/// No element matches the selector.
+	NoMatch {
+		/// The selector string.
+		selector: String,
+	},
+
+	/// More than one element matches the selector.
+	MultipleMatches {
+		/// The selector string.
+		selector: String,
+	},
+}
+
+impl fmt::Display for SelectError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			SelectError::InvalidSelector(error) => write!(f, "invalid selector: {}", error),
+			SelectError::NoMatch { selector } => write!(f, "no element matches `{}`", selector),
+			SelectError::MultipleMatches { selector } => {
+				write!(f, "more than one element matches `{}`", selector)
+			}
+		}
+	}
+}
+
+impl Error for SelectError {
+	fn source(&self) -> Option<&(dyn Error + 'static)> {
+		match self {
+			SelectError::InvalidSelector(error) => Some(error),
+			_ => None,
+		}
+	}
+}
+
+impl From<SelectorError> for SelectError {
+	fn from(error: SelectorError) -> Self {
+		SelectError::InvalidSelector(error)
+	}
+}
+
 impl Selectors {
 	/// Compile a list of selectors. This may fail on syntax errors or unsupported selectors.
 	#[inline]
-	pub fn compile(s: &str) -> Result<Selectors, ()> {
+	pub fn compile(s: &str) -> Result<Selectors, SelectorError> {
 		let mut input = cssparser::ParserInput::new(s);
 		match SelectorList::parse(&KuchikiParser, &mut cssparser::Parser::new(&mut input)) {
 			Ok(list) => Ok(Selectors(list.0.into_iter().map(Selector).collect())),
-			Err(_) => Err(()),
+			Err(error) => Err(SelectorError::new(error)),
 		}
 	}
 
 	/// Returns whether the given element matches this list of selectors.
 	#[inline]
 	pub fn matches(&self, element: &NodeDataRef<ElementData>) -> bool {
-		self.0.iter().any(|s| s.matches(element))
+		self.matches_in_scope(element, None)
+	}
+
+	/// Returns whether the given element matches this list of selectors,
+	/// with `:scope` matching the given node.
+	///
+	/// Without a scope, `:scope` matches the root element, like `:root`.
+	#[inline]
+	pub fn matches_in_scope(
+		&self,
+		element: &NodeDataRef<ElementData>,
+		scope: Option<&NodeRef>,
+	) -> bool {
+		self.matches_with_opts(element, scope, &MatchOpts::default())
+	}
+
+	/// Returns whether the given element matches this list of selectors,
+	/// with `:scope` matching the given node and the given options.
+	#[inline]
+	pub fn matches_with_opts(
+		&self,
+		element: &NodeDataRef<ElementData>,
+		scope: Option<&NodeRef>,
+		opts: &MatchOpts,
+	) -> bool {
+		self.0
+			.iter()
+			.any(|s| s.matches_with_opts(element, scope, opts))
 	}
 
 	/// Filter an element iterator, yielding those matching this list of selectors.
@@ -361,6 +715,8 @@ impl Selectors {
 		Select {
 			iter,
 			selectors: self,
+			scope: None,
+			opts: MatchOpts::default(),
 		}
 	}
 }
@@ -369,25 +725,135 @@ impl Selector {
 	/// Returns whether the given element matches this selector.
 	#[inline]
 	pub fn matches(&self, element: &NodeDataRef<ElementData>) -> bool {
-		let mut context = matching::MatchingContext::new(
-			matching::MatchingMode::Normal,
-			None,
-			None,
-			QuirksMode::NoQuirks,
-		);
-		matching::matches_selector(&self.0, 0, None, element, &mut context, &mut |_, _| {})
+		self.matches_in_scope(element, None)
+	}
+
+	/// Returns whether the given element matches this selector,
+	/// with `:scope` matching the given node.
+	///
+	/// Without a scope, `:scope` matches the root element, like `:root`.
+	#[inline]
+	pub fn matches_in_scope(
+		&self,
+		element: &NodeDataRef<ElementData>,
+		scope: Option<&NodeRef>,
+	) -> bool {
+		self.matches_with_opts(element, scope, &MatchOpts::default())
+	}
+
+	/// Returns whether the given element matches this selector,
+	/// with `:scope` matching the given node and the given options.
+	pub fn matches_with_opts(
+		&self,
+		element: &NodeDataRef<ElementData>,
+		scope: Option<&NodeRef>,
+		opts: &MatchOpts,
+	) -> bool {
+		// Quirks mode only changes how classes and IDs are compared,
+		// attribute values are compared by `attr_matches` according to `ATTR_CASE_SENSITIVITY`.
+		let quirks_mode = match opts.case_sensitivity {
+			CaseSensitivity::CaseSensitive => QuirksMode::NoQuirks,
+			CaseSensitivity::AsciiCaseInsensitive => QuirksMode::Quirks,
+		};
+		let mut context =
+			matching::MatchingContext::new(matching::MatchingMode::Normal, None, None, quirks_mode);
+		context.scope_element = scope.map(|node| OpaqueElement::new::<Node>(node));
+		let previous = ATTR_CASE_SENSITIVITY.with(|forced| forced.replace(opts.case_sensitivity));
+		let matches =
+			matching::matches_selector(&self.0, 0, None, element, &mut context, &mut |_, _| {});
+		ATTR_CASE_SENSITIVITY.with(|forced| forced.set(previous));
+		matches
 	}
 
 	/// Return the specificity of this selector.
+	///
+	/// A `:has()` pseudo-class counts as its most specific argument.
 	pub fn specificity(&self) -> Specificity {
-		Specificity(self.0.specificity())
+		fn add_relative(components: &[Component<KuchikiSelectors>], specificity: &mut u32) {
+			for component in components {
+				match *component {
+					Component::NonTSPseudoClass(PseudoClass::Has(ref relative)) => {
+						*specificity = add_specificities(*specificity, relative.specificity)
+					}
+					Component::Negation(ref negated) => add_relative(negated, specificity),
+					_ => {}
+				}
+			}
+		}
+		let mut specificity = self.0.specificity();
+		add_relative(self.0.iter_raw_match_order().as_slice(), &mut specificity);
+		Specificity(specificity)
+	}
+}
+
+/// The specificity that the `selectors` crate gives to `:scope`, a pseudo-class.
+const SCOPE_SPECIFICITY: u32 = 1 << 10;
+
+/// Add two specificities packed by the `selectors` crate as ten bits per count
+/// of IDs, classes and elements, saturating each count.
+fn add_specificities(a: u32, b: u32) -> u32 {
+	const MAX: u32 = (1 << 10) - 1;
+	[20, 10, 0].iter().fold(0, |sum, &shift| {
+		let count = ((a >> shift) & MAX) + ((b >> shift) & MAX);
+		sum | (count.min(MAX) << shift)
+	})
+}
+
+/// A cache of compiled selector lists, keyed by their source string,
+/// for code that matches the same selector strings against many documents.
+///
+/// The cache is `Send` and `Sync`, but compiling a new entry needs `&mut self`:
+/// share it between threads behind a lock, or keep one per thread.
+/// Trees themselves are not `Send`, so each thread matches against its own documents.
+#[derive(Default)]
+pub struct SelectorCache {
+	compiled: HashMap<String, Selectors>,
+}
+
+impl SelectorCache {
+	/// Create an empty cache.
+	pub fn new() -> SelectorCache {
+		SelectorCache::default()
+	}
+
+	/// Return the compiled form of the given selector list,
+	/// compiling it on first use.
+	///
+	/// Errors are not cached, so an invalid selector is compiled again on every call.
+	pub fn get_or_compile(&mut self, selectors: &str) -> Result<&Selectors, SelectorError> {
+		if !self.compiled.contains_key(selectors) {
+			let compiled = Selectors::compile(selectors)?;
+			self.compiled.insert(selectors.to_owned(), compiled);
+		}
+		Ok(&self.compiled[selectors])
+	}
+
+	/// Return the number of cached selector lists.
+	pub fn len(&self) -> usize {
+		self.compiled.len()
+	}
+
+	/// Return whether the cache is empty.
+	pub fn is_empty(&self) -> bool {
+		self.compiled.is_empty()
+	}
+
+	/// Remove every cached selector list.
+	pub fn clear(&mut self) {
+		self.compiled.clear()
+	}
+}
+
+impl fmt::Debug for SelectorCache {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.debug_set().entries(self.compiled.keys()).finish()
 	}
 }
 
 impl ::std::str::FromStr for Selectors {
-	type Err = ();
+	type Err = SelectorError;
 	#[inline]
-	fn from_str(s: &str) -> Result<Selectors, ()> {
+	fn from_str(s: &str) -> Result<Selectors, SelectorError> {
 		Selectors::compile(s)
 	}
 }
@@ -400,13 +866,10 @@ impl fmt::Display for Selector {
 
 impl fmt::Display for Selectors {
 	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-		let mut iter = self.0.iter();
-		let first = iter
-			.next()
-			.expect("Empty Selectors, should contain at least one selector");
-		first.0.to_css(f)?;
-		for selector in iter {
-			f.write_str(", ")?;
+		for (i, selector) in self.0.iter().enumerate() {
+			if i > 0 {
+				f.write_str(", ")?;
+			}
 			selector.0.to_css(f)?;
 		}
 		Ok(())
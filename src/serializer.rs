@@ -1,13 +1,18 @@
 use html5ever::serialize::TraversalScope::*;
 use html5ever::serialize::{serialize, Serialize, SerializeOpts, Serializer, TraversalScope};
-use html5ever::QualName;
+use html5ever::{LocalName, Namespace, Prefix, QualName};
+use std::borrow::Cow;
+use std::collections::HashSet;
 use std::fmt;
 use std::fs::File;
 use std::io;
 use std::io::Write;
 use std::path::Path;
 
-use crate::tree::{NodeData, NodeRef};
+use crate::attributes::Attributes;
+use crate::iter::NodeEdge;
+use crate::select::Selectors;
+use crate::tree::{Doctype, ElementData, NodeData, NodeRef};
 
 impl Serialize for NodeRef {
 	fn serialize<S: Serializer>(
@@ -15,66 +20,110 @@ impl Serialize for NodeRef {
 		serializer: &mut S,
 		traversal_scope: TraversalScope,
 	) -> io::Result<()> {
-		match (traversal_scope, self.data()) {
-			(ref scope, NodeData::Element(element)) => {
-				if *scope == IncludeNode {
-					let attrs = element.attributes.borrow();
-
-					// Unfortunately we need to allocate something to hold these &'a QualName
-					let attrs = attrs
-						.map
-						.iter()
-						.map(|(name, attr)| {
-							(
-								QualName::new(
-									attr.prefix.clone(),
-									name.ns.clone(),
-									name.local.clone(),
-								),
-								&attr.value,
-							)
-						})
-						.collect::<Vec<_>>();
-
-					serializer.start_elem(
-						element.name.clone(),
-						attrs.iter().map(|&(ref name, value)| (name, &**value)),
-					)?
-				}
+		serialize_node(
+			self,
+			serializer,
+			traversal_scope,
+			&HtmlSerializeOpts::default(),
+		)
+	}
+}
 
-				let children = match element.template_contents.as_ref() {
-					Some(template_root) => template_root.children(),
-					None => self.children(),
-				};
+/// A node paired with the HTML serialization options to apply to it.
+struct WithOpts<'a> {
+	node: &'a NodeRef,
+	opts: &'a HtmlSerializeOpts,
+}
 
-				for child in children {
-					Serialize::serialize(&child, serializer, IncludeNode)?
-				}
+impl Serialize for WithOpts<'_> {
+	fn serialize<S: Serializer>(
+		&self,
+		serializer: &mut S,
+		traversal_scope: TraversalScope,
+	) -> io::Result<()> {
+		serialize_node(self.node, serializer, traversal_scope, self.opts)
+	}
+}
 
-				if *scope == IncludeNode {
-					serializer.end_elem(element.name.clone())?
-				}
-				Ok(())
+fn serialize_node<S: Serializer>(
+	root: &NodeRef,
+	serializer: &mut S,
+	traversal_scope: TraversalScope,
+	opts: &HtmlSerializeOpts,
+) -> io::Result<()> {
+	// Walk the tree with an explicit stack instead of recursing, to support deep trees.
+	// An `End` edge is only pushed for elements whose tags are written.
+	let mut stack = Vec::new();
+	match traversal_scope {
+		IncludeNode => stack.push(NodeEdge::Start(root.clone())),
+		ChildrenOnly(_) => stack.extend(serialized_children(root).rev().map(NodeEdge::Start)),
+	}
+	while let Some(edge) = stack.pop() {
+		let node = match edge {
+			NodeEdge::Start(node) => node,
+			NodeEdge::End(node) => {
+				serializer.end_elem(node.as_element().unwrap().name.clone())?;
+				continue;
 			}
-
-			(_, &NodeData::DocumentFragment) | (_, &NodeData::Document(_)) => {
-				for child in self.children() {
-					Serialize::serialize(&child, serializer, IncludeNode)?
+		};
+		match node.data() {
+			NodeData::Element(element) => {
+				let action = match &opts.element_filter {
+					Some(filter) => filter(element),
+					None => ElementAction::Keep,
+				};
+				match action {
+					ElementAction::Remove => continue,
+					ElementAction::Unwrap => {}
+					ElementAction::Keep => {
+						start_element(serializer, element, opts)?;
+						stack.push(NodeEdge::End(node.clone()));
+					}
 				}
-				Ok(())
+				stack.extend(serialized_children(&node).rev().map(NodeEdge::Start));
 			}
-
-			(ChildrenOnly(_), _) => Ok(()),
-
-			(IncludeNode, NodeData::Doctype(doctype)) => serializer.write_doctype(&doctype.name),
-			(IncludeNode, NodeData::Text(text)) => serializer.write_text(&text.borrow()),
-			(IncludeNode, NodeData::Comment(text)) => serializer.write_comment(&text.borrow()),
-			(IncludeNode, NodeData::ProcessingInstruction(contents)) => {
-				let contents = contents.borrow();
-				serializer.write_processing_instruction(&contents.0, &contents.1)
+			NodeData::DocumentFragment | NodeData::Document(_) => {
+				stack.extend(node.children().rev().map(NodeEdge::Start));
+			}
+			NodeData::Doctype(doctype) => {
+				serializer.write_doctype(&DoctypeDisplay(doctype).to_string())?
+			}
+			NodeData::Text(text) => serializer.write_text(&text.borrow())?,
+			NodeData::Comment(text) => serializer.write_comment(&text.borrow())?,
+			NodeData::ProcessingInstruction(contents) => {
+				let (target, data) = &*contents.borrow();
+				serializer.write_processing_instruction(target, data)?
 			}
 		}
 	}
+	Ok(())
+}
+
+fn start_element<S: Serializer>(
+	serializer: &mut S,
+	element: &ElementData,
+	opts: &HtmlSerializeOpts,
+) -> io::Result<()> {
+	let attrs = element.attributes.borrow();
+
+	// Unfortunately we need to allocate something to hold these &'a QualName
+	let attrs = attrs
+		.map
+		.iter()
+		.filter_map(|(name, attr)| {
+			let name = QualName::new(attr.prefix.clone(), name.ns.clone(), name.local.clone());
+			let value = match &opts.attribute_filter {
+				Some(filter) => Cow::Owned(filter(&name, &attr.value)?),
+				None => Cow::Borrowed(&*attr.value),
+			};
+			Some((name, value))
+		})
+		.collect::<Vec<_>>();
+
+	serializer.start_elem(
+		element.name.clone(),
+		attrs.iter().map(|(name, value)| (name, &**value)),
+	)
 }
 
 impl fmt::Display for NodeRef {
@@ -102,6 +151,62 @@ impl NodeRef {
 		)
 	}
 
+	/// Serialize this node and its descendants in HTML syntax to a new `String`.
+	///
+	/// Unlike `to_string()`, errors from the serializer are returned rather than
+	/// collapsed into `fmt::Error`.
+	pub fn serialize_to_string(&self) -> io::Result<String> {
+		let mut bytes = Vec::new();
+		self.serialize(&mut bytes)?;
+		String::from_utf8(bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+	}
+
+	/// Serialize this node and its descendants in HTML syntax into the given buffer,
+	/// replacing its contents.
+	///
+	/// The buffer is cleared first and keeps its allocation,
+	/// so reusing it across calls avoids growing a new buffer for every serialization.
+	pub fn serialize_into_vec(&self, buf: &mut Vec<u8>) -> io::Result<()> {
+		buf.clear();
+		self.serialize(buf)
+	}
+
+	/// Serialize this node and its descendants in HTML syntax to a new `String`,
+	/// like the DOM `outerHTML` getter.
+	///
+	/// Serializing to memory does not fail in practice, so this returns the `String` directly,
+	/// like `to_string()`. Use `serialize_to_string` to handle errors instead.
+	#[doc(alias = "outer_html")]
+	#[doc(alias = "outerHTML")]
+	#[inline]
+	pub fn html(&self) -> String {
+		self.to_string()
+	}
+
+	/// Serialize this node’s children in HTML syntax to the given stream,
+	/// excluding the node itself.
+	///
+	/// For `<template>` elements, this serializes the template contents.
+	#[inline]
+	pub fn serialize_inner<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+		serialize(
+			writer,
+			self,
+			SerializeOpts {
+				traversal_scope: children_only(self),
+				..Default::default()
+			},
+		)
+	}
+
+	/// Serialize this node’s children in HTML syntax to a new `String`,
+	/// like the DOM `innerHTML` getter.
+	pub fn inner_html(&self) -> io::Result<String> {
+		let mut bytes = Vec::new();
+		self.serialize_inner(&mut bytes)?;
+		String::from_utf8(bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+	}
+
 	/// Serialize this node and its descendants in HTML syntax to a new file at the given path.
 	#[inline]
 	pub fn serialize_to_file<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
@@ -109,3 +214,565 @@ impl NodeRef {
 		self.serialize(&mut file)
 	}
 }
+
+impl NodeRef {
+	/// Serialize in HTML syntax only the parts of this subtree that lead to elements
+	/// matching `keep`, such as an article with its contents and its ancestors.
+	///
+	/// A matching element is written with all its descendants.
+	/// Its ancestors up to this node are written with their attributes but without other children.
+	/// The doctype of a document is kept, and nothing is written if no element matches.
+	/// As with `select`, template contents are not searched for matches.
+	pub fn serialize_matching<W: Write>(&self, keep: &Selectors, writer: &mut W) -> io::Result<()> {
+		serialize(
+			writer,
+			&Matching { node: self, keep },
+			SerializeOpts {
+				traversal_scope: IncludeNode,
+				..Default::default()
+			},
+		)
+	}
+}
+
+/// A node to serialize pruned to the elements matching `keep`, and their ancestors.
+struct Matching<'a> {
+	node: &'a NodeRef,
+	keep: &'a Selectors,
+}
+
+impl Serialize for Matching<'_> {
+	#[allow(clippy::mutable_key_type)]
+	fn serialize<S: Serializer>(&self, serializer: &mut S, _: TraversalScope) -> io::Result<()> {
+		let matches = self
+			.node
+			.select_compiled(self.keep)
+			.map(|element| element.as_node().clone())
+			.collect::<HashSet<_>>();
+		let mut ancestors = HashSet::new();
+		for element in &matches {
+			for ancestor in element.ancestors() {
+				if !ancestors.insert(ancestor.clone()) || ancestor == *self.node {
+					break;
+				}
+			}
+		}
+
+		let opts = HtmlSerializeOpts::default();
+		let mut stack = vec![NodeEdge::Start(self.node.clone())];
+		while let Some(edge) = stack.pop() {
+			let node = match edge {
+				NodeEdge::Start(node) => node,
+				NodeEdge::End(node) => {
+					serializer.end_elem(node.as_element().unwrap().name.clone())?;
+					continue;
+				}
+			};
+			if matches.contains(&node) {
+				serialize_node(&node, serializer, IncludeNode, &opts)?;
+				continue;
+			}
+			if !ancestors.contains(&node) {
+				if let NodeData::Doctype(doctype) = node.data() {
+					serializer.write_doctype(&DoctypeDisplay(doctype).to_string())?
+				}
+				continue;
+			}
+			match node.data() {
+				NodeData::Element(element) => {
+					start_element(serializer, element, &opts)?;
+					stack.push(NodeEdge::End(node.clone()));
+					stack.extend(node.children().rev().map(NodeEdge::Start));
+				}
+				_ => stack.extend(node.children().rev().map(NodeEdge::Start)),
+			}
+		}
+		Ok(())
+	}
+}
+
+/// Serialize each of the given nodes and its descendants in HTML syntax, in order,
+/// to the given stream.
+///
+/// This accepts `NodeRef`s as well as the elements yielded by `select`.
+pub fn serialize_nodes<I, W>(nodes: I, writer: &mut W) -> io::Result<()>
+where
+	I: IntoIterator,
+	I::Item: Into<NodeRef>,
+	W: Write,
+{
+	for node in nodes {
+		node.into().serialize(writer)?
+	}
+	Ok(())
+}
+
+/// A callback deciding how each attribute is serialized, given its name and value:
+/// `None` omits the attribute and `Some` replaces its value.
+pub type AttributeFilter = dyn Fn(&QualName, &str) -> Option<String>;
+
+/// A callback deciding whether each element is serialized.
+pub type ElementFilter = dyn Fn(&ElementData) -> ElementAction;
+
+/// What to do with an element during serialization, as returned by an `ElementFilter`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ElementAction {
+	/// Serialize the element normally.
+	Keep,
+	/// Serialize the element’s children but not its tags.
+	Unwrap,
+	/// Skip the element and its descendants.
+	Remove,
+}
+
+/// Options for HTML serialization.
+///
+/// These only affect the output; the tree itself is left unchanged.
+#[derive(Default)]
+pub struct HtmlSerializeOpts {
+	/// Called for every attribute of every serialized element.
+	pub attribute_filter: Option<Box<AttributeFilter>>,
+	/// Called for every serialized element.
+	/// When serializing only a node’s children, it is not called for the node itself.
+	pub element_filter: Option<Box<ElementFilter>>,
+	/// Write a UTF-8 byte order mark before the output.
+	pub byte_order_mark: bool,
+	/// End the output with a newline, unless it already ends with one.
+	pub trailing_newline: bool,
+}
+
+impl fmt::Debug for HtmlSerializeOpts {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.debug_struct("HtmlSerializeOpts")
+			.field(
+				"attribute_filter",
+				&self.attribute_filter.as_ref().map(|_| ".."),
+			)
+			.field(
+				"element_filter",
+				&self.element_filter.as_ref().map(|_| ".."),
+			)
+			.field("byte_order_mark", &self.byte_order_mark)
+			.field("trailing_newline", &self.trailing_newline)
+			.finish()
+	}
+}
+
+impl NodeRef {
+	/// Serialize this node and its descendants in HTML syntax to the given stream,
+	/// applying the given options.
+	pub fn serialize_with_opts<W: Write>(
+		&self,
+		writer: &mut W,
+		opts: &HtmlSerializeOpts,
+	) -> io::Result<()> {
+		serialize_with_opts(self, writer, opts, IncludeNode)
+	}
+
+	/// Serialize this node’s children in HTML syntax to the given stream,
+	/// excluding the node itself and applying the given options.
+	pub fn serialize_inner_with_opts<W: Write>(
+		&self,
+		writer: &mut W,
+		opts: &HtmlSerializeOpts,
+	) -> io::Result<()> {
+		serialize_with_opts(self, writer, opts, children_only(self))
+	}
+
+	/// Serialize this node and its descendants in HTML syntax to a new file at the given path,
+	/// applying the given options.
+	pub fn serialize_to_file_with_opts<P: AsRef<Path>>(
+		&self,
+		path: P,
+		opts: &HtmlSerializeOpts,
+	) -> io::Result<()> {
+		let mut file = File::create(&path)?;
+		self.serialize_with_opts(&mut file, opts)
+	}
+}
+
+/// The traversal scope for serializing the children of `node`.
+///
+/// The serializer chooses how to escape text from the name of its parent element,
+/// so this names `node` to keep the contents of raw text elements like `<script>` unescaped.
+fn children_only(node: &NodeRef) -> TraversalScope {
+	ChildrenOnly(node.as_element().map(|element| element.name.clone()))
+}
+
+fn serialize_with_opts<W: Write>(
+	node: &NodeRef,
+	writer: &mut W,
+	opts: &HtmlSerializeOpts,
+	traversal_scope: TraversalScope,
+) -> io::Result<()> {
+	if opts.byte_order_mark {
+		writer.write_all("\u{FEFF}".as_bytes())?;
+	}
+	let mut writer = LastByte { writer, last: None };
+	serialize(
+		&mut writer,
+		&WithOpts { node, opts },
+		SerializeOpts {
+			traversal_scope,
+			..Default::default()
+		},
+	)?;
+	if opts.trailing_newline && writer.last != Some(b'\n') {
+		writer.write_all(b"\n")?;
+	}
+	Ok(())
+}
+
+/// A writer that remembers the last byte written through it.
+struct LastByte<'w, W: Write> {
+	writer: &'w mut W,
+	last: Option<u8>,
+}
+
+impl<W: Write> Write for LastByte<'_, W> {
+	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+		let written = self.writer.write(buf)?;
+		if written > 0 {
+			self.last = Some(buf[written - 1]);
+		}
+		Ok(written)
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		self.writer.flush()
+	}
+}
+
+/// Options for XML serialization.
+#[derive(Debug, Clone, Default)]
+pub struct XmlSerializeOpts {
+	/// Write an `<?xml version="1.0" encoding="UTF-8"?>` declaration before the output.
+	pub xml_declaration: bool,
+	/// The local names of elements whose text children are written as CDATA sections
+	/// instead of being escaped, such as `script` and `style` in SVG.
+	///
+	/// A `]]>` in the text is split across two sections.
+	pub cdata_elements: Vec<LocalName>,
+}
+
+impl NodeRef {
+	/// Serialize this node and its descendants in XML syntax to the given stream,
+	/// with the default options.
+	#[inline]
+	pub fn serialize_xml<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+		self.serialize_xml_with_opts(writer, &XmlSerializeOpts::default())
+	}
+
+	/// Serialize this node and its descendants in XML syntax to the given stream.
+	///
+	/// Elements without children are written as self-closing tags,
+	/// and namespace declarations are added where the tree’s prefixes need them.
+	pub fn serialize_xml_with_opts<W: Write>(
+		&self,
+		writer: &mut W,
+		opts: &XmlSerializeOpts,
+	) -> io::Result<()> {
+		if opts.xml_declaration {
+			writer.write_all(b"<?xml version=\"1.0\" encoding=\"UTF-8\"?>")?;
+		}
+		XmlSerializer {
+			writer,
+			opts,
+			scopes: Vec::new(),
+		}
+		.serialize(self)
+	}
+}
+
+/// Return the children to serialize for a node, which for templates are the template contents.
+fn serialized_children(node: &NodeRef) -> impl DoubleEndedIterator<Item = NodeRef> {
+	match node
+		.as_element()
+		.and_then(|element| element.template_contents.as_ref())
+	{
+		Some(template_root) => template_root.children(),
+		None => node.children(),
+	}
+}
+
+struct XmlSerializer<'w, W: Write> {
+	writer: &'w mut W,
+	opts: &'w XmlSerializeOpts,
+	/// Namespace declarations made by each open element.
+	scopes: Vec<Vec<(Option<Prefix>, Namespace)>>,
+}
+
+impl<W: Write> XmlSerializer<'_, W> {
+	fn serialize(&mut self, root: &NodeRef) -> io::Result<()> {
+		let mut stack = vec![NodeEdge::Start(root.clone())];
+		while let Some(edge) = stack.pop() {
+			match edge {
+				NodeEdge::Start(node) => {
+					let mut children = serialized_children(&node).peekable();
+					match node.data() {
+						NodeData::Element(element) => {
+							let is_empty = children.peek().is_none();
+							self.start_element(element, is_empty)?;
+							if is_empty {
+								continue;
+							}
+						}
+						NodeData::Text(text) if self.in_cdata_element(&node) => {
+							self.write_cdata(&text.borrow())?
+						}
+						NodeData::Text(text) => self.write_escaped(&text.borrow(), false)?,
+						NodeData::Comment(text) => write!(self.writer, "<!--{}-->", text.borrow())?,
+						NodeData::ProcessingInstruction(contents) => {
+							let (target, data) = &*contents.borrow();
+							if data.is_empty() {
+								write!(self.writer, "<?{}?>", target)?
+							} else {
+								write!(self.writer, "<?{} {}?>", target, data)?
+							}
+						}
+						NodeData::Doctype(doctype) => {
+							write!(self.writer, "<!DOCTYPE {}>", DoctypeDisplay(doctype))?
+						}
+						NodeData::Document(_) | NodeData::DocumentFragment => {}
+					}
+					stack.push(NodeEdge::End(node));
+					stack.extend(children.rev().map(NodeEdge::Start));
+				}
+				NodeEdge::End(node) => {
+					if let Some(element) = node.as_element() {
+						self.scopes.pop();
+						self.writer.write_all(b"</")?;
+						write_qualified_name(
+							self.writer,
+							&element.name.prefix,
+							&element.name.local,
+						)?;
+						self.writer.write_all(b">")?;
+					}
+				}
+			}
+		}
+		Ok(())
+	}
+
+	fn start_element(&mut self, element: &ElementData, is_empty: bool) -> io::Result<()> {
+		let attributes = element.attributes.borrow();
+
+		// Declarations present as `xmlns` attributes apply to the element itself.
+		let mut scope = Vec::new();
+		for (name, attr) in &attributes.map {
+			if name.ns == ns!(xmlns) {
+				let prefix = attr.prefix.as_ref().map(|_| Prefix::from(&*name.local));
+				scope.push((prefix, Namespace::from(&*attr.value)));
+			}
+		}
+
+		let mut declarations = Vec::new();
+		self.declare(
+			&mut scope,
+			&mut declarations,
+			&element.name.prefix,
+			&element.name.ns,
+		);
+		let attribute_prefixes = attributes
+			.map
+			.iter()
+			.map(|(name, attr)| {
+				if name.ns == ns!() || name.ns == ns!(xmlns) {
+					attr.prefix.clone()
+				} else {
+					let prefix = attr
+						.prefix
+						.clone()
+						.or_else(|| self.bound_prefix(&scope, &name.ns))
+						.or_else(|| Some(self.unused_prefix(&scope, &attributes, &name.ns)));
+					self.declare(&mut scope, &mut declarations, &prefix, &name.ns);
+					prefix
+				}
+			})
+			.collect::<Vec<_>>();
+
+		self.writer.write_all(b"<")?;
+		write_qualified_name(self.writer, &element.name.prefix, &element.name.local)?;
+		for (prefix, ns) in declarations {
+			match prefix {
+				Some(prefix) => write!(self.writer, " xmlns:{}=\"", prefix)?,
+				None => self.writer.write_all(b" xmlns=\"")?,
+			}
+			self.write_escaped(&ns, true)?;
+			self.writer.write_all(b"\"")?;
+		}
+		for ((name, attr), prefix) in attributes.map.iter().zip(attribute_prefixes) {
+			self.writer.write_all(b" ")?;
+			write_qualified_name(self.writer, &prefix, &name.local)?;
+			self.writer.write_all(b"=\"")?;
+			self.write_escaped(&attr.value, true)?;
+			self.writer.write_all(b"\"")?;
+		}
+		if is_empty {
+			self.writer.write_all(b"/>")
+		} else {
+			self.scopes.push(scope);
+			self.writer.write_all(b">")
+		}
+	}
+
+	/// Return a non-empty prefix already bound to `ns`, if any.
+	fn bound_prefix(
+		&self,
+		scope: &[(Option<Prefix>, Namespace)],
+		ns: &Namespace,
+	) -> Option<Prefix> {
+		scope
+			.iter()
+			.rev()
+			.chain(
+				self.scopes
+					.iter()
+					.rev()
+					.flat_map(|scope| scope.iter().rev()),
+			)
+			.find(|(p, n)| p.is_some() && n == ns)
+			.and_then(|(p, _)| p.clone())
+	}
+
+	/// Return a prefix for a namespaced attribute that does not have one:
+	/// the usual prefix of `ns`, or else the first of `ns0`, `ns1`… that is unused,
+	/// neither bound in scope nor written on one of the element’s attributes.
+	fn unused_prefix(
+		&self,
+		scope: &[(Option<Prefix>, Namespace)],
+		attributes: &Attributes,
+		ns: &Namespace,
+	) -> Prefix {
+		let is_unused = |prefix: &Prefix| {
+			let used = Some(prefix);
+			!scope
+				.iter()
+				.chain(self.scopes.iter().flatten())
+				.any(|(p, _)| p.as_ref() == used)
+				&& !attributes
+					.map
+					.iter()
+					.any(|(name, attr)| name.ns != ns!(xmlns) && attr.prefix.as_ref() == used)
+		};
+		default_prefix(ns)
+			.into_iter()
+			.chain((0..).map(|index| Prefix::from(format!("ns{}", index))))
+			.find(is_unused)
+			.unwrap()
+	}
+
+	/// Add a declaration for `prefix` if it is not already bound to `ns` where it is used.
+	fn declare(
+		&self,
+		scope: &mut Vec<(Option<Prefix>, Namespace)>,
+		declarations: &mut Vec<(Option<Prefix>, Namespace)>,
+		prefix: &Option<Prefix>,
+		ns: &Namespace,
+	) {
+		if *ns == ns!(xml) || *ns == ns!(xmlns) {
+			return;
+		}
+		let in_scope = scope
+			.iter()
+			.rev()
+			.chain(
+				self.scopes
+					.iter()
+					.rev()
+					.flat_map(|scope| scope.iter().rev()),
+			)
+			.find(|(p, _)| p == prefix)
+			.map_or(ns!(), |(_, ns)| ns.clone());
+		if in_scope != *ns {
+			scope.push((prefix.clone(), ns.clone()));
+			declarations.push((prefix.clone(), ns.clone()));
+		}
+	}
+
+	/// Return whether `node` is a child of an element listed in `cdata_elements`.
+	fn in_cdata_element(&self, node: &NodeRef) -> bool {
+		node.parent().is_some_and(|parent| {
+			parent
+				.as_element()
+				.is_some_and(|element| self.opts.cdata_elements.contains(&element.name.local))
+		})
+	}
+
+	fn write_cdata(&mut self, text: &str) -> io::Result<()> {
+		let text = text.replace("]]>", "]]]]><![CDATA[>");
+		write!(self.writer, "<![CDATA[{}]]>", text)
+	}
+
+	/// Escape `text` for XML character data, or for a double-quoted attribute value.
+	///
+	/// Carriage returns, and in attributes tabs and newlines, are written as character references
+	/// so that the whitespace normalization of XML parsers does not change them.
+	fn write_escaped(&mut self, text: &str, attr_mode: bool) -> io::Result<()> {
+		let mut start = 0;
+		for (i, c) in text.char_indices() {
+			let escaped = match c {
+				'&' => "&amp;",
+				'<' => "&lt;",
+				'>' if !attr_mode => "&gt;",
+				'"' if attr_mode => "&quot;",
+				'\r' => "&#13;",
+				'\t' if attr_mode => "&#9;",
+				'\n' if attr_mode => "&#10;",
+				_ => continue,
+			};
+			self.writer.write_all(&text.as_bytes()[start..i])?;
+			self.writer.write_all(escaped.as_bytes())?;
+			start = i + 1;
+		}
+		self.writer.write_all(&text.as_bytes()[start..])
+	}
+}
+
+fn write_qualified_name<W: Write>(
+	writer: &mut W,
+	prefix: &Option<Prefix>,
+	local: &LocalName,
+) -> io::Result<()> {
+	if let Some(prefix) = prefix {
+		write!(writer, "{}:", prefix)?;
+	}
+	writer.write_all(local.as_bytes())
+}
+
+/// The usual prefix of a namespace, if it has one.
+fn default_prefix(ns: &Namespace) -> Option<Prefix> {
+	match *ns {
+		ns!(xlink) => Some(namespace_prefix!("xlink")),
+		ns!(svg) => Some(namespace_prefix!("svg")),
+		ns!(html) => Some(namespace_prefix!("html")),
+		_ => None,
+	}
+}
+
+/// Formats the contents of a doctype: its name, then its public and system identifiers if any.
+///
+/// Identifiers are quoted with `"`, or with `'` if they contain `"`.
+struct DoctypeDisplay<'a>(&'a Doctype);
+
+impl fmt::Display for DoctypeDisplay<'_> {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		fn quoted(f: &mut fmt::Formatter, id: &str) -> fmt::Result {
+			let quote = if id.contains('"') { '\'' } else { '"' };
+			write!(f, " {}{}{}", quote, id, quote)
+		}
+		let doctype = self.0;
+		f.write_str(&doctype.name)?;
+		if !doctype.public_id.is_empty() {
+			f.write_str(" PUBLIC")?;
+			quoted(f, &doctype.public_id)?;
+		} else if !doctype.system_id.is_empty() {
+			f.write_str(" SYSTEM")?;
+		}
+		if !doctype.system_id.is_empty() {
+			quoted(f, &doctype.system_id)?;
+		}
+		Ok(())
+	}
+}
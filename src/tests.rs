@@ -1,12 +1,21 @@
-use html5ever::tree_builder::QuirksMode;
-use html5ever::QualName;
+use html5ever::tree_builder::{NodeOrText, QuirksMode, TreeSink};
+use html5ever::{Namespace, QualName};
+use indexmap::map::Entry;
+use std::io::{self, Read};
 use std::path::Path;
 
 use tempfile::TempDir;
 
-use crate::parser::{parse_fragment, parse_html};
+use crate::attributes::ExpandedName;
+use crate::parser::{
+	parse_fragment, parse_html, parse_html_from_bytes, parse_html_from_reader,
+	parse_html_with_limits, parse_xml, AdjacentPosition, FragmentError, LimitError, ParseLimits,
+};
 use crate::select::*;
+use crate::serializer::{serialize_nodes, ElementAction, HtmlSerializeOpts, XmlSerializeOpts};
 use crate::traits::*;
+use crate::tree::{Node, NodeRef};
+use crate::CaseSensitivity;
 
 #[test]
 fn text_nodes() {
@@ -38,6 +47,55 @@ fn text_nodes() {
 	assert_eq!(&*texts[0].borrow(), "Content doesn't contain ");
 }
 
+#[test]
+fn inner_text() {
+	let html = r"<title>Title</title><style>p {}</style>
+<div><p>First   paragraph<br>with a <b>bold</b>
+word.</p><p>Second</p></div><ul><li>One<li> Two </ul>
+<pre>  keep
+  this</pre>text<script>ignored()</script>";
+	let document = parse_html().one(html);
+	assert_eq!(
+		document.inner_text(),
+		"First paragraph\nwith a bold word.\nSecond\nOne\nTwo\n  keep\n  this\ntext"
+	);
+	let p = document.select_first("p").unwrap().unwrap();
+	assert_eq!(
+		p.as_node().inner_text(),
+		"First paragraph\nwith a bold word."
+	);
+}
+
+#[test]
+fn text_contents_visible() {
+	let html = r"<style>p { color: red }</style><p>Visible <b>text</b></p>
+<script>var x = 1;</script><noscript>No script</noscript><template>T</template>end";
+	let document = parse_html().one(html);
+	assert_eq!(document.text_contents_visible(), "Visible text\nend");
+	assert!(document.text_contents().contains("var x = 1;"));
+}
+
+#[test]
+fn replace_text() {
+	let html = r#"<p title="foo">foo bar foo</p><script>foo()</script><p>fo<b>o</b></p>"#;
+	let document = parse_html().one(html);
+	document.replace_text("foo", "baz");
+	let body = document.select_first("body").unwrap().unwrap();
+	assert_eq!(
+		body.as_node().inner_html().unwrap(),
+		r#"<p title="foo">baz bar baz</p><script>foo()</script><p>fo<b>o</b></p>"#
+	);
+}
+
+#[cfg(feature = "regex")]
+#[test]
+fn replace_text_regex() {
+	let document = parse_html().one("<p>2024-01-31</p><style>p { x: 1-2-3 }</style>");
+	let regex = regex::Regex::new(r"(\d+)-(\d+)-(\d+)").unwrap();
+	document.replace_text_regex(&regex, "$3/$2/$1");
+	assert_eq!(document.text_contents(), "31/01/2024p { x: 1-2-3 }");
+}
+
 #[test]
 fn parse_and_serialize() {
 	let html = r"
@@ -56,6 +114,25 @@ fn parse_and_serialize() {
 	);
 }
 
+#[test]
+fn serialize_doctype() {
+	let html = r#"<!DOCTYPE html PUBLIC "-//W3C//DTD HTML 4.01 Transitional//EN" "http://www.w3.org/TR/html4/loose.dtd"><html><head></head><body></body></html>"#;
+	assert_eq!(parse_html().one(html).to_string(), html);
+
+	let html =
+		r#"<!DOCTYPE html SYSTEM "about:legacy-compat"><html><head></head><body></body></html>"#;
+	assert_eq!(parse_html().one(html).to_string(), html);
+
+	let html = r"<!DOCTYPE html><html><head></head><body></body></html>";
+	assert_eq!(parse_html().one(html).to_string(), html);
+
+	// An identifier containing `"` can only be written in single quotes.
+	let html = r#"<!DOCTYPE html PUBLIC 'say "hi"' "sys"><html><head></head><body></body></html>"#;
+	assert_eq!(parse_html().one(html).to_string(), html);
+	let html = r#"<!DOCTYPE html SYSTEM 'a"b'><html><head></head><body></body></html>"#;
+	assert_eq!(parse_html().one(html).to_string(), html);
+}
+
 #[test]
 fn parse_and_serialize_with_template() {
 	let html = r"
@@ -90,6 +167,189 @@ fn parse_and_serialize_fragment() {
 	);
 }
 
+#[test]
+fn parse_xml_document() {
+	let xml = r##"<?xml version="1.0"?>
+<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink">
+<linearGradient id="g"/><use xlink:href="#g"/><br/>Text</svg>"##;
+	let document = parse_xml().one(xml);
+	let svg = document.select_first("svg").unwrap().unwrap();
+	assert_eq!(svg.name.ns, ns!(svg));
+
+	// Element names keep their case and self-closing elements have no children.
+	let gradient = document.select_first("linearGradient").unwrap().unwrap();
+	assert!(gradient.as_node().first_child().is_none());
+	let br = document.select_first("br").unwrap().unwrap();
+	assert_eq!(br.name.ns, ns!(svg));
+	assert_eq!(
+		br.as_node()
+			.next_sibling()
+			.unwrap()
+			.as_text()
+			.unwrap()
+			.borrow()
+			.as_str(),
+		"Text"
+	);
+
+	let use_element = document.select_first("use").unwrap().unwrap();
+	let attributes = use_element.attributes.borrow();
+	let href = &attributes.map[&ExpandedName::new(ns!(xlink), local_name!("href"))];
+	assert_eq!(href.prefix, Some(namespace_prefix!("xlink")));
+	assert_eq!(href.value, "#g");
+
+	// Children of XHTML templates go to the template contents, as with the HTML parser.
+	let xhtml =
+		r#"<html xmlns="http://www.w3.org/1999/xhtml"><template><p>x</p></template></html>"#;
+	let document = parse_xml().one(xhtml);
+	let template = document.select_first("template").unwrap().unwrap();
+	assert!(template.as_node().first_child().is_none());
+	assert_eq!(template.as_node().inner_html().unwrap(), "<p>x</p>");
+}
+
+#[test]
+fn serialize_with_attribute_filter() {
+	let html =
+		r#"<p onclick="evil()" title="t"><a href="javascript:evil()">a</a><a href="/ok">b</a></p>"#;
+	let document = parse_html().one(html);
+	let opts = HtmlSerializeOpts {
+		attribute_filter: Some(Box::new(|name, value| {
+			if name.local.starts_with("on") {
+				None
+			} else if value.trim_start().starts_with("javascript:") {
+				Some("#".to_owned())
+			} else {
+				Some(value.to_owned())
+			}
+		})),
+		..Default::default()
+	};
+	let p = document.select_first("p").unwrap().unwrap();
+	let mut bytes = Vec::new();
+	p.as_node().serialize_with_opts(&mut bytes, &opts).unwrap();
+	assert_eq!(
+		String::from_utf8(bytes).unwrap(),
+		r##"<p title="t"><a href="#">a</a><a href="/ok">b</a></p>"##
+	);
+	// The tree itself is untouched.
+	assert_eq!(p.get_attribute("onclick"), Some("evil()".to_owned()));
+}
+
+#[test]
+fn serialize_with_element_filter() {
+	let html =
+		"<div><script>evil()</script><font><b>bold</b> text</font><iframe><p>x</p></iframe></div>";
+	let document = parse_html().one(html);
+	let opts = HtmlSerializeOpts {
+		element_filter: Some(Box::new(|element| match &*element.name.local {
+			"script" | "iframe" => ElementAction::Remove,
+			"font" => ElementAction::Unwrap,
+			_ => ElementAction::Keep,
+		})),
+		..Default::default()
+	};
+	let div = document.select_first("div").unwrap().unwrap();
+	let serialize = |inner: bool| {
+		let mut bytes = Vec::new();
+		if inner {
+			div.as_node().serialize_inner_with_opts(&mut bytes, &opts)
+		} else {
+			div.as_node().serialize_with_opts(&mut bytes, &opts)
+		}
+		.unwrap();
+		String::from_utf8(bytes).unwrap()
+	};
+	assert_eq!(serialize(false), "<div><b>bold</b> text</div>");
+	assert_eq!(serialize(true), "<b>bold</b> text");
+	assert_eq!(document.select("script").unwrap().count(), 1);
+}
+
+#[test]
+fn serialize_selected_nodes() {
+	let document = parse_html().one("<p>a</p><div>b</div><p>c <b>d</b></p>");
+	let mut bytes = Vec::new();
+	serialize_nodes(document.select("p").unwrap(), &mut bytes).unwrap();
+	assert_eq!(
+		String::from_utf8(bytes).unwrap(),
+		"<p>a</p><p>c <b>d</b></p>"
+	);
+
+	let mut bytes = Vec::new();
+	let div = document.select_first("div").unwrap().unwrap();
+	serialize_nodes(div.as_node().children(), &mut bytes).unwrap();
+	assert_eq!(bytes, b"b");
+}
+
+#[test]
+fn serialize_deep_tree() {
+	let depth = 100_000;
+	let root = NodeRef::new_element(QualName::new(None, ns!(html), local_name!("div")), vec![]);
+	let mut parent = root.clone();
+	for _ in 1..depth {
+		let child =
+			NodeRef::new_element(QualName::new(None, ns!(html), local_name!("div")), vec![]);
+		parent.append(child.clone());
+		parent = child;
+	}
+	parent.append(NodeRef::new_text("x"));
+	let html = root.serialize_to_string().unwrap();
+	assert_eq!(html.len(), depth * "<div></div>".len() + 1);
+	assert!(html.starts_with("<div><div>"));
+	assert!(html.contains("<div>x</div></div>"));
+	assert_eq!(
+		root.inner_html().unwrap().len(),
+		html.len() - "<div></div>".len()
+	);
+}
+
+#[test]
+fn serialize_xml() {
+	let xml = r##"<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink">
+<?pi data?><g id="a"></g><use xlink:href="#a"/><text>a &lt; b</text></svg>"##;
+	let document = parse_xml().one(xml);
+	let mut bytes = Vec::new();
+	document.serialize_xml(&mut bytes).unwrap();
+	// The parser consumes `xmlns` attributes, so declarations are written where first needed.
+	assert_eq!(
+		String::from_utf8(bytes).unwrap(),
+		r##"<svg xmlns="http://www.w3.org/2000/svg">
+<?pi data?><g id="a"/><use xmlns:xlink="http://www.w3.org/1999/xlink" xlink:href="#a"/><text>a &lt; b</text></svg>"##
+	);
+
+	// Namespaces of HTML-parsed content are declared, and void elements are self-closing.
+	let document = parse_html().one(r##"<p>x<br><svg><use xlink:href="#a"></use></svg>"##);
+	let p = document.select_first("p").unwrap().unwrap();
+	let mut bytes = Vec::new();
+	p.as_node()
+		.serialize_xml_with_opts(
+			&mut bytes,
+			&XmlSerializeOpts {
+				xml_declaration: true,
+				..Default::default()
+			},
+		)
+		.unwrap();
+	assert_eq!(
+		String::from_utf8(bytes).unwrap(),
+		r##"<?xml version="1.0" encoding="UTF-8"?><p xmlns="http://www.w3.org/1999/xhtml">x<br/><svg xmlns="http://www.w3.org/2000/svg"><use xmlns:xlink="http://www.w3.org/1999/xlink" xlink:href="#a"/></svg></p>"##
+	);
+
+	// Generated prefixes skip the ones already in use.
+	let document = parse_xml().one(r#"<r xmlns:ns0="urn:a" ns0:x="1"><c/></r>"#);
+	let c = document.select_first("c").unwrap().unwrap();
+	{
+		let mut attributes = c.attributes.borrow_mut();
+		attributes.insert_ns(Namespace::from("urn:b"), None, "y", "2".to_owned());
+		attributes.insert_ns(Namespace::from("urn:c"), None, "z", "3".to_owned());
+	}
+	let mut bytes = Vec::new();
+	document.serialize_xml(&mut bytes).unwrap();
+	assert_eq!(
+		String::from_utf8(bytes).unwrap(),
+		r#"<r xmlns:ns0="urn:a" ns0:x="1"><c xmlns:ns1="urn:b" xmlns:ns2="urn:c" ns1:y="2" ns2:z="3"/></r>"#
+	);
+}
+
 #[test]
 fn parse_file() {
 	let mut path = Path::new(env!("CARGO_MANIFEST_DIR")).to_path_buf();
@@ -108,6 +368,157 @@ fn parse_file() {
 	assert_eq!(document.to_string(), html);
 }
 
+#[test]
+fn parse_with_limits() {
+	let parse = |html: &str, max_depth, max_nodes| {
+		let limits = ParseLimits {
+			max_depth,
+			max_nodes,
+		};
+		parse_html_with_limits(Default::default(), limits).one(html)
+	};
+
+	let deep = "<div>".repeat(100);
+	// html, body, and the divs.
+	assert_eq!(parse(&deep, Some(50), None), Err(LimitError::TooDeep));
+	assert!(parse(&deep, Some(102), None).is_ok());
+
+	let templates = "<template>".repeat(100);
+	assert_eq!(parse(&templates, Some(50), None), Err(LimitError::TooDeep));
+
+	let many = "<p>x</p>".repeat(20);
+	// head, html, body and the paragraphs.
+	assert_eq!(parse(&many, None, Some(10)), Err(LimitError::TooManyNodes));
+	let document = parse(&many, None, Some(23)).unwrap();
+	assert!(document.deep_eq(&parse_html().one(many)));
+}
+
+#[test]
+fn limited_sink_stops_creating_nodes() {
+	let limits = ParseLimits {
+		max_depth: None,
+		max_nodes: Some(0),
+	};
+	let mut parser = parse_html_with_limits(Default::default(), limits);
+	let sink = &mut parser.tokenizer.sink.sink;
+	let name = |local| QualName::new(None, ns!(html), local);
+	let div = sink.create_element(name(local_name!("div")), vec![], Default::default());
+	let other_div = sink.create_element(name(local_name!("div")), vec![], Default::default());
+	let p = sink.create_element(name(local_name!("p")), vec![], Default::default());
+	assert!(div == other_div);
+	assert!(div != p);
+	assert_eq!(sink.elem_name(&p).local, &local_name!("p"));
+	assert!(sink.create_comment("a".into()) == sink.create_comment("b".into()));
+
+	let document = sink.get_document();
+	sink.append(&document, NodeOrText::AppendNode(div.clone()));
+	assert!(document.first_child().is_none());
+	assert_eq!(parser.one("<p>more</p>"), Err(LimitError::TooManyNodes));
+
+	// The tree builder keeps working with placeholders for the rest of the input.
+	let messy = "<table><b><tr><td>1<i>2</b>3</table><template><p>4</template><svg><desc><p>5";
+	for max_nodes in 0..12 {
+		let limits = ParseLimits {
+			max_depth: None,
+			max_nodes: Some(max_nodes),
+		};
+		let result = parse_html_with_limits(Default::default(), limits).one(messy);
+		assert_eq!(result, Err(LimitError::TooManyNodes));
+	}
+}
+
+#[test]
+fn limited_sink_checks_depth_of_moved_nodes() {
+	let parse = |max_depth| {
+		let limits = ParseLimits {
+			max_depth: Some(max_depth),
+			max_nodes: None,
+		};
+		let mut parser = parse_html_with_limits(Default::default(), limits);
+		let sink = &mut parser.tokenizer.sink.sink;
+		let mut element = || {
+			sink.create_element(
+				QualName::new(None, ns!(html), local_name!("div")),
+				vec![],
+				Default::default(),
+			)
+		};
+		let (holder, a, b, target) = (element(), element(), element(), element());
+		sink.append(&a, NodeOrText::AppendNode(b));
+		sink.append(&holder, NodeOrText::AppendNode(a));
+		let document = sink.get_document();
+		sink.append(&document, NodeOrText::AppendNode(target.clone()));
+		// `a` and `b` move to depths 2 and 3, below `target`.
+		sink.reparent_children(&holder, &target);
+		let moved = target.descendants().count();
+		parser.one("").map(|_| moved)
+	};
+	assert_eq!(parse(2), Err(LimitError::TooDeep));
+	// The document has `target` besides the `<html>` element that an empty input inserts.
+	assert_eq!(parse(3), Ok(2));
+}
+
+#[test]
+fn parse_from_reader() {
+	/// Yields one byte per read, splitting multi-byte characters across reads.
+	struct ByteByByte<'a>(&'a [u8]);
+
+	impl Read for ByteByByte<'_> {
+		fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+			match self.0.split_first() {
+				Some((&byte, rest)) if !buf.is_empty() => {
+					buf[0] = byte;
+					self.0 = rest;
+					Ok(1)
+				}
+				_ => Ok(0),
+			}
+		}
+	}
+
+	let html = "<!DOCTYPE html><title>Café</title><p>口利き";
+	let document = parse_html_from_reader(ByteByByte(html.as_bytes())).unwrap();
+	assert_eq!(
+		document.to_string(),
+		"<!DOCTYPE html><html><head><title>Café</title></head><body><p>口利き</p></body></html>"
+	);
+}
+
+#[test]
+fn parse_from_bytes() {
+	let text = |document: NodeRef| document.select_first("p").unwrap().unwrap().text_contents();
+
+	// windows-1252 "Café", declared in a `<meta charset>`.
+	let bytes = b"<meta charset=windows-1252><p>Caf\xe9";
+	assert_eq!(text(parse_html_from_bytes(bytes, None)), "Café");
+
+	// Shift_JIS "日本", declared with `http-equiv`.
+	let bytes = b"<meta http-equiv='Content-Type' content='text/html; charset=Shift_JIS'>\
+		<p>\x93\xfa\x96\x7b";
+	assert_eq!(text(parse_html_from_bytes(bytes, None)), "日本");
+
+	// An explicit encoding is used when there is no byte order mark.
+	let bytes = b"<p>\x93\xfa\x96\x7b";
+	assert_eq!(
+		text(parse_html_from_bytes(bytes, Some(encoding_rs::SHIFT_JIS))),
+		"日本"
+	);
+
+	// A byte order mark takes precedence over everything else.
+	let bytes = b"\xef\xbb\xbf<meta charset=windows-1252><p>Caf\xc3\xa9";
+	assert_eq!(
+		text(parse_html_from_bytes(bytes, Some(encoding_rs::SHIFT_JIS))),
+		"Café"
+	);
+
+	// Undeclared input is UTF-8 if valid, windows-1252 otherwise.
+	assert_eq!(
+		text(parse_html_from_bytes("<p>口利き".as_bytes(), None)),
+		"口利き"
+	);
+	assert_eq!(text(parse_html_from_bytes(b"<p>Caf\xe9", None)), "Café");
+}
+
 #[test]
 fn serialize_and_read_file() {
 	let tempdir = TempDir::new().unwrap();
@@ -123,87 +534,2436 @@ fn serialize_and_read_file() {
 }
 
 #[test]
-fn select() {
-	let html = r"
-<title>Test case</title>
-<p class=foo>Foo
-<p>Bar
-<p class=foo>Foo
-";
+fn serialize_to_string() {
+	let html = r"<!DOCTYPE html><html><head><title>Title</title></head><body>Body</body></html>";
+	let document = parse_html().one(html);
+	assert_eq!(document.serialize_to_string().unwrap(), html);
+	assert_eq!(
+		document.serialize_to_string().unwrap(),
+		document.to_string()
+	);
+}
 
+#[test]
+fn inner_html() {
+	let html = r"<div id=a><p>One</p>Two<!--c--></div><template><b>T</b></template>";
 	let document = parse_html().one(html);
-	let matching = document.select("p.foo").unwrap().collect::<Vec<_>>();
-	assert_eq!(matching.len(), 2);
-	let child = matching[0].as_node().first_child().unwrap();
-	assert_eq!(&**child.as_text().unwrap().borrow(), "Foo\n");
-	assert_eq!(matching[0].attributes.borrow().get("class"), Some("foo"));
+	let div = document.select_first("div").unwrap().unwrap();
+	assert_eq!(div.as_node().inner_html().unwrap(), "<p>One</p>Two<!--c-->");
+	let template = document.select_first("template").unwrap().unwrap();
+	assert_eq!(template.as_node().inner_html().unwrap(), "<b>T</b>");
+	let text = div.as_node().first_child().unwrap().next_sibling().unwrap();
+	assert_eq!(text.inner_html().unwrap(), "");
+}
+
+#[test]
+fn set_inner_html() {
+	let html = r"<div><p>Old</p></div><table></table>";
+	let document = parse_html().one(html);
+	let div = document.select_first("div").unwrap().unwrap();
+	div.as_node().set_inner_html("<b>New</b> text").unwrap();
+	assert_eq!(div.as_node().inner_html().unwrap(), "<b>New</b> text");
+
+	// `<tr>` is only kept when parsed in a table context.
+	let table = document.select_first("table").unwrap().unwrap();
+	table.as_node().set_inner_html("<tr><td>Cell").unwrap();
 	assert_eq!(
-		matching[0].attributes.borrow().get(local_name!("class")),
-		Some("foo")
+		table.as_node().inner_html().unwrap(),
+		"<tbody><tr><td>Cell</td></tr></tbody>"
 	);
+	div.as_node().set_inner_html("<tr><td>Cell").unwrap();
+	assert_eq!(div.as_node().inner_html().unwrap(), "Cell");
 
-	let selectors = Selectors::compile("p.foo").unwrap();
-	let matching2 = selectors
-		.filter(document.descendants().elements())
-		.collect::<Vec<_>>();
-	assert_eq!(matching, matching2);
+	let text = NodeRef::new_text("text");
+	assert_eq!(text.set_inner_html("<p>"), Err(FragmentError::NotAnElement));
 }
 
 #[test]
-fn select_first() {
-	let html = r"
-<title>Test case</title>
-<p class=foo>Foo
-<p>Bar
-<p class=foo>Baz
-";
+fn append_and_prepend_html() {
+	let document = parse_html().one("<table><tbody><tr><td>2</td></tr></tbody></table>");
+	let tbody = document.select_first("tbody").unwrap().unwrap();
+	let tbody = tbody.as_node();
+	tbody
+		.append_html("<tr><td>3</td></tr><tr><td>4</td></tr>")
+		.unwrap();
+	tbody
+		.prepend_html("<tr><td>0</td></tr><tr><td>1</td></tr>")
+		.unwrap();
+	assert_eq!(
+		tbody.serialize_to_string().unwrap(),
+		"<tbody><tr><td>0</td></tr><tr><td>1</td></tr><tr><td>2</td></tr>\
+		 <tr><td>3</td></tr><tr><td>4</td></tr></tbody>"
+	);
+
+	let template = parse_html().one("<template><p>b</p></template>");
+	let template = template.select_first("template").unwrap().unwrap();
+	template.as_node().prepend_html("<p>a</p>").unwrap();
+	assert_eq!(template.as_node().inner_html().unwrap(), "<p>a</p><p>b</p>");
+
+	assert_eq!(
+		document.append_html("<p>x</p>"),
+		Err(FragmentError::NotAnElement)
+	);
+}
+
+#[test]
+fn insert_adjacent_html() {
+	let document = parse_html().one("<div><p id=target>x</p></div>");
+	let target = document.get_element_by_id("target").unwrap();
+	target
+		.insert_adjacent_html(AdjacentPosition::BeforeBegin, "<b>1</b><b>2</b>")
+		.unwrap();
+	target
+		.insert_adjacent_html(AdjacentPosition::AfterBegin, "<i>3</i>")
+		.unwrap();
+	target
+		.insert_adjacent_html(AdjacentPosition::BeforeEnd, "<i>4</i>")
+		.unwrap();
+	target
+		.insert_adjacent_html(AdjacentPosition::AfterEnd, "<b>5</b><b>6</b>")
+		.unwrap();
+	let div = document.select_first("div").unwrap().unwrap();
+	assert_eq!(
+		div.as_node().inner_html().unwrap(),
+		r#"<b>1</b><b>2</b><p id="target"><i>3</i>x<i>4</i></p><b>5</b><b>6</b>"#
+	);
+
+	// Sibling positions are parsed in the parent's context.
+	let row = parse_html().one("<table><tr><td>a</td></tr></table>");
+	let td = row.select_first("td").unwrap().unwrap();
+	td.as_node()
+		.insert_adjacent_html(AdjacentPosition::AfterEnd, "<td>b</td>")
+		.unwrap();
+	let tr = row.select_first("tr").unwrap().unwrap();
+	assert_eq!(tr.as_node().inner_html().unwrap(), "<td>a</td><td>b</td>");
+
+	let detached = NodeRef::new_element(QualName::new(None, ns!(html), local_name!("p")), vec![]);
+	assert_eq!(
+		detached.insert_adjacent_html(AdjacentPosition::AfterEnd, "x"),
+		Err(FragmentError::NoParent)
+	);
+}
+
+#[test]
+fn class_list() {
+	let document = parse_html().one(r#"<p class="a  b">x</p><div>y</div>"#);
+	let p = document.select_first("p").unwrap().unwrap();
+	{
+		let mut attributes = p.attributes.borrow_mut();
+		let mut classes = attributes.class_list();
+		assert!(classes.contains("a"));
+		assert!(!classes.contains("c"));
+		classes.add("c");
+		classes.add("a");
+		assert!(classes.remove("b"));
+		assert!(!classes.remove("b"));
+		assert!(!classes.toggle("a"));
+		assert!(classes.toggle("d"));
+		assert_eq!(classes.iter().collect::<Vec<_>>(), ["c", "d"]);
+	}
+	assert_eq!(p.attributes.borrow().get("class"), Some("c d"));
+	assert!(document.select_first("p.c.d").unwrap().is_some());
+	assert!(document.select_first("p.a").unwrap().is_none());
+	assert!(document.select_first("p.b").unwrap().is_none());
+
+	let div = document.select_first("div").unwrap().unwrap();
+	div.attributes.borrow_mut().class_list().add("single");
+	assert_eq!(div.attributes.borrow().get("class"), Some("single"));
+	assert!(document.select_first("div.single").unwrap().is_some());
+}
+
+#[test]
+fn class_cache_follows_attribute_mutation() {
+	let document = parse_html().one(r#"<p class="a b">x</p>"#);
+	let p = document.select_first("p").unwrap().unwrap();
+	let has = |selector| document.select_first(selector).unwrap().is_some();
+	assert!(has("p.a"));
+
+	p.attributes.borrow_mut().insert("class", "c d".to_owned());
+	assert!(!has("p.a"));
+	assert!(has("p.c.d"));
+
+	p.attributes
+		.borrow_mut()
+		.get_mut("class")
+		.unwrap()
+		.push_str(" e");
+	assert!(has("p.e"));
+
+	if let Entry::Occupied(mut entry) = p.attributes.borrow_mut().entry("class") {
+		entry.get_mut().value = "f".to_owned();
+	}
+	assert!(has("p.f"));
+	assert!(!has("p.c"));
+
+	p.attributes.borrow_mut().remove("class");
+	assert!(!has("p.f"));
+}
+
+#[test]
+fn class_cache_case_insensitive() {
+	use selectors::attr::CaseSensitivity::{AsciiCaseInsensitive, CaseSensitive};
+
+	let document = parse_html().one(r#"<p class="Foo bar">x</p>"#);
+	let p = document.select_first("p").unwrap().unwrap();
+	let attributes = p.attributes.borrow();
+	assert!(attributes.has_class(b"foo", AsciiCaseInsensitive));
+	assert!(attributes.has_class(b"FOO", AsciiCaseInsensitive));
+	assert!(attributes.has_class(b"BAR", AsciiCaseInsensitive));
+	assert!(attributes.has_class(b"Foo", CaseSensitive));
+	assert!(!attributes.has_class(b"foo", CaseSensitive));
+	assert!(!attributes.has_class(b"baz", AsciiCaseInsensitive));
+}
+
+#[test]
+fn class_bloom_filter_false_positive_rate() {
+	use crate::attributes::ClassCache;
 
+	let classes: Vec<_> = (0..40).map(|i| format!("class-{}", i)).collect();
+	let html = format!(r#"<p class="{}">x</p>"#, classes.join(" "));
 	let document = parse_html().one(html);
-	let matching = document.select_first("p.foo").unwrap();
-	let child = matching.as_node().first_child().unwrap();
-	assert_eq!(&**child.as_text().unwrap().borrow(), "Foo\n");
-	assert_eq!(matching.attributes.borrow().get("class"), Some("foo"));
+	let p = document.select_first("p").unwrap().unwrap();
+	let attributes = p.attributes.borrow();
+	let bloom_filter = match &attributes.class_cache {
+		Some(ClassCache::Bloom(bloom_filter)) => bloom_filter,
+		_ => panic!("expected a Bloom filter"),
+	};
+	for class in &classes {
+		assert!(bloom_filter.contains(class.as_bytes()));
+	}
+	let false_positives = (0..10_000)
+		.filter(|i| bloom_filter.contains(format!("other-{}", i).as_bytes()))
+		.count();
+	// With a fixed 64 bits, about half of these probes would be false positives.
+	assert!(false_positives < 100, "{} false positives", false_positives);
+}
+
+#[test]
+fn element_attribute_methods() {
+	let document = parse_html().one(r#"<p id="a"></p>"#);
+	let p = document.select_first("p").unwrap().unwrap();
+	assert!(p.has_attribute("id"));
+	assert_eq!(p.get_attribute("id"), Some("a".to_owned()));
+	assert_eq!(p.get_attribute("title"), None);
+
+	p.set_attribute("class", "x y");
+	assert!(document.select_first("p.y").unwrap().is_some());
+	assert_eq!(p.remove_attribute("class"), Some("x y".to_owned()));
+	assert!(document.select_first("p.y").unwrap().is_none());
+	assert!(!p.has_attribute("class"));
+	assert_eq!(p.remove_attribute("class"), None);
+}
+
+#[test]
+fn iterate_attributes() {
+	let document = parse_html().one(r#"<p id="a" class="x y" title="t"></p><br>"#);
+	let p = document.select_first("p").unwrap().unwrap();
+	let mut attributes = p.attributes.borrow_mut();
+	assert_eq!(attributes.len(), 3);
+	let names: Vec<_> = attributes.iter().map(|(name, _)| &*name.local).collect();
+	assert_eq!(names, ["id", "class", "title"]);
+
+	for (_, attr) in attributes.iter_mut() {
+		attr.value.make_ascii_uppercase();
+	}
+	assert_eq!(attributes.get("class"), Some("X Y"));
+	drop(attributes);
+	assert!(document.select_first("p.Y").unwrap().is_some());
+	assert!(document.select_first("p.y").unwrap().is_none());
+
+	let br = document.select_first("br").unwrap().unwrap();
+	assert!(br.attributes.borrow().is_empty());
+}
+
+#[test]
+fn dataset() {
+	let document = parse_html().one(r#"<div data-user-id="42" data-x="y" id="d"></div>"#);
+	let div = document.select_first("div").unwrap().unwrap();
+	let mut attributes = div.attributes.borrow_mut();
+	assert_eq!(attributes.get_data("userId"), Some("42"));
+	assert_eq!(attributes.get_data("id"), None);
+
+	attributes.set_data("fooBar", "baz".to_owned());
+	assert_eq!(attributes.get("data-foo-bar"), Some("baz"));
 	assert_eq!(
-		matching.attributes.borrow().get(local_name!("class")),
-		Some("foo")
+		attributes.dataset().collect::<Vec<_>>(),
+		vec![
+			("userId".to_owned(), "42"),
+			("x".to_owned(), "y"),
+			("fooBar".to_owned(), "baz"),
+		]
 	);
 
-	assert!(document.select_first("p.bar").is_err());
+	assert!(attributes.remove_data("x").is_some());
+	assert!(!attributes.contains("data-x"));
 }
 
 #[test]
-fn to_string() {
-	let html = r"<!DOCTYPE html>
-<html>
-    <head>
-        <title>Test case</title>
-    </head>
-    <body>
-        <p class=foo>Foo
-    </body>
-</html>";
+fn namespaced_attributes() {
+	let document = parse_html().one("<svg><use/></svg>");
+	let use_element = document.select_first("use").unwrap().unwrap();
+	{
+		let mut attributes = use_element.attributes.borrow_mut();
+		assert!(!attributes.contains_ns(ns!(xlink), "href"));
+		attributes.insert_ns(
+			ns!(xlink),
+			Some(namespace_prefix!("xlink")),
+			"href",
+			"#target".to_owned(),
+		);
+		assert!(attributes.contains_ns(ns!(xlink), "href"));
+		assert_eq!(attributes.get_ns(ns!(xlink), "href"), Some("#target"));
+		assert_eq!(attributes.get("href"), None);
+	}
+
+	let mut xml = Vec::new();
+	use_element.as_node().serialize_xml(&mut xml).unwrap();
+	assert_eq!(
+		String::from_utf8(xml).unwrap(),
+		r##"<use xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink" xlink:href="#target"/>"##
+	);
+	assert_eq!(
+		use_element.as_node().serialize_to_string().unwrap(),
+		r##"<use xlink:href="#target"></use>"##
+	);
+
+	let removed = use_element
+		.attributes
+		.borrow_mut()
+		.remove_ns(ns!(xlink), "href")
+		.unwrap();
+	assert_eq!(removed.prefix, Some(namespace_prefix!("xlink")));
+	assert!(!use_element
+		.attributes
+		.borrow()
+		.contains_ns(ns!(xlink), "href"));
+}
+
+#[test]
+fn rename_attribute() {
+	let document = parse_html().one(r#"<p data-old="1" class="x" title="t" data-new="2"></p>"#);
+	let p = document.select_first("p").unwrap().unwrap();
+	let name = |local: &str| ExpandedName::new(ns!(), local);
+	let names = || {
+		p.attributes
+			.borrow()
+			.iter()
+			.map(|(name, attr)| format!("{}={}", name.local, attr.value))
+			.collect::<Vec<_>>()
+	};
 
+	assert!(p
+		.attributes
+		.borrow_mut()
+		.rename(name("data-old"), name("data-new")));
+	assert_eq!(names(), ["data-new=1", "class=x", "title=t"]);
+	assert!(!p
+		.attributes
+		.borrow_mut()
+		.rename(name("data-old"), name("data-new")));
+
+	p.attributes
+		.borrow_mut()
+		.rename(name("title"), name("class"));
+	assert_eq!(names(), ["data-new=1", "class=t"]);
+	assert!(document.select_first("p.t").unwrap().is_some());
+	assert!(document.select_first("p.x").unwrap().is_none());
+
+	p.attributes
+		.borrow_mut()
+		.rename(name("class"), name("title"));
+	assert!(document.select_first("p.t").unwrap().is_none());
+}
+
+#[test]
+fn get_element_by_id() {
+	let html = r#"<div id="a"><p id="b">1</p><p id="b">2</p></div>"#;
 	let document = parse_html().one(html);
+	let b = document.get_element_by_id("b").unwrap();
+	assert_eq!(b.text_contents(), "1");
+	assert!(document.get_element_by_id("c").is_none());
+
+	let a = document.get_element_by_id("a").unwrap();
+	assert_eq!(a.get_element_by_id("a"), Some(a.clone()));
+	b.detach();
+	assert_eq!(
+		document.get_element_by_id("b").unwrap().text_contents(),
+		"2"
+	);
+}
+
+#[test]
+fn select_compiled() {
+	let selectors = Selectors::compile("p.title").unwrap();
+	for i in 0..3 {
+		let html = format!(r#"<p class="title">{}</p><p>other</p>"#, i);
+		let document = parse_html().one(html);
+		let matches: Vec<_> = document.select_compiled(&selectors).collect();
+		assert_eq!(matches.len(), 1);
+		assert_eq!(matches[0].text_contents(), i.to_string());
+	}
+}
+
+#[test]
+fn get_elements_by_tag_name() {
+	let document = parse_html()
+		.one(r#"<div><P>a</P><p>b</p><svg><foreignObject/><foreignobject/></svg></div>"#);
+	let div = document.select_first("div").unwrap().unwrap();
+	let texts: Vec<_> = div
+		.as_node()
+		.get_elements_by_tag_name("P")
+		.map(|p| p.text_contents())
+		.collect();
+	assert_eq!(texts, ["a", "b"]);
+	assert_eq!(div.as_node().get_elements_by_tag_name("div").count(), 0);
+	assert_eq!(
+		document.get_elements_by_tag_name("foreignObject").count(),
+		2
+	);
+	assert_eq!(div.as_node().get_elements_by_tag_name("*").count(), 5);
 	assert_eq!(
 		document
-			.inclusive_descendants()
-			.nth(11)
+			.get_elements_by_tag_name("p")
+			.next_back()
 			.unwrap()
-			.to_string(),
-		"<p class=\"foo\">Foo\n    \n</p>"
+			.text_contents(),
+		"b"
 	);
 }
 
 #[test]
-fn specificity() {
-	let selectors = Selectors::compile(".example, :first-child, div").unwrap();
-	let specificities = selectors
-		.0
-		.iter()
-		.map(|s| s.specificity())
-		.collect::<Vec<_>>();
-	assert_eq!(specificities.len(), 3);
-	assert!(specificities[0] == specificities[1]);
-	assert!(specificities[0] > specificities[2]);
+fn get_elements_by_class_name() {
+	use crate::CaseSensitivity::{AsciiCaseInsensitive, CaseSensitive};
+
+	let document = parse_html().one(
+		r#"<p class="a b">1</p><p class="B">2</p><div class="b c"><span class="b">3</span></div>"#,
+	);
+	let texts = |class, case_sensitivity| {
+		document
+			.get_elements_by_class_name(class, case_sensitivity)
+			.map(|element| element.text_contents())
+			.collect::<Vec<_>>()
+	};
+	assert_eq!(texts("b", CaseSensitive), ["1", "3", "3"]);
+	assert_eq!(texts("b", AsciiCaseInsensitive), ["1", "2", "3", "3"]);
+	assert_eq!(texts("d", CaseSensitive), Vec::<String>::new());
+}
+
+#[test]
+fn bounded_ancestors() {
+	let document = parse_html().one(
+		r#"<table><tr><td><div class="nested"><div class="nested"><b>x</b></div></div></td></tr></table>"#,
+	);
+	let b = document.select_first("b").unwrap().unwrap();
+	let names = |ancestors: &mut dyn Iterator<Item = NodeRef>| {
+		ancestors
+			.map(|node| node.as_element().unwrap().name.local.to_string())
+			.collect::<Vec<_>>()
+	};
+
+	let nested = Selectors::compile(".nested").unwrap();
+	let mut while_nested = b.as_node().ancestors_while(|node| {
+		node.clone()
+			.into_element_ref()
+			.is_some_and(|element| nested.matches(&element))
+	});
+	assert_eq!(names(&mut while_nested), ["div", "div"]);
+
+	let table = Selectors::compile("table").unwrap();
+	let mut until_table = b.as_node().ancestors_until(&table);
+	assert_eq!(names(&mut until_table), ["div", "div", "td", "tr", "tbody"]);
+
+	let none = Selectors::compile("span").unwrap();
+	assert_eq!(b.as_node().ancestors_until(&none).count(), 9);
+}
+
+#[test]
+fn element_siblings() {
+	let document = parse_html().one("<p>a</p> text <!-- c --><p>b</p>\n<p>c</p>");
+	let paragraphs: Vec<_> = document.select("p").unwrap().collect();
+	let (first, middle, last) = (
+		paragraphs[0].as_node(),
+		paragraphs[1].as_node(),
+		paragraphs[2].as_node(),
+	);
+	assert_eq!(first.next_element_sibling().as_ref(), Some(middle));
+	assert_eq!(middle.previous_element_sibling().as_ref(), Some(first));
+	assert_eq!(first.previous_element_sibling(), None);
+	assert_eq!(last.next_element_sibling(), None);
+
+	let texts = |elements: &mut dyn Iterator<Item = crate::NodeDataRef<crate::ElementData>>| {
+		elements
+			.map(|element| element.text_contents())
+			.collect::<Vec<_>>()
+	};
+	assert_eq!(texts(&mut first.following_elements()), ["b", "c"]);
+	assert_eq!(texts(&mut last.preceding_elements()), ["b", "a"]);
+}
+
+#[test]
+fn element_children() {
+	let document = parse_html().one("<ul>\n<li>a</li> <!-- c --> <li>b</li>\n</ul>");
+	let ul = document.select_first("ul").unwrap().unwrap();
+	let ul = ul.as_node();
+	assert!(ul.first_child().unwrap().as_text().is_some());
+	assert_eq!(ul.first_element_child().unwrap().text_contents(), "a");
+	assert_eq!(ul.last_element_child().unwrap().text_contents(), "b");
+	assert_eq!(ul.child_elements().count(), 2);
+
+	let li = ul.first_element_child().unwrap();
+	assert_eq!(li.first_element_child(), None);
+	assert_eq!(li.last_element_child(), None);
+}
+
+#[test]
+fn contains() {
+	let document = parse_html().one("<aside><p>a</p></aside><p>b</p>");
+	let aside = document.select_first("aside").unwrap().unwrap();
+	let paragraphs: Vec<_> = document.select("p").unwrap().collect();
+	assert!(aside.as_node().contains(paragraphs[0].as_node()));
+	assert!(!aside.as_node().contains(paragraphs[1].as_node()));
+	assert!(aside.as_node().contains(aside.as_node()));
+	assert!(!paragraphs[0].as_node().contains(aside.as_node()));
+	assert!(document.contains(paragraphs[1].as_node()));
+}
+
+#[test]
+fn compare_document_position() {
+	use std::cmp::Ordering::*;
+
+	let document = parse_html().one("<div id=a><p id=b>x</p></div><p id=c><i id=d></i></p>");
+	let node = |id| document.get_element_by_id(id).unwrap();
+	let (a, b, c, d) = (node("a"), node("b"), node("c"), node("d"));
+	assert_eq!(a.compare_document_position(&a), Some(Equal));
+	assert_eq!(a.compare_document_position(&b), Some(Less));
+	assert_eq!(b.compare_document_position(&a), Some(Greater));
+	assert_eq!(b.compare_document_position(&d), Some(Less));
+	assert_eq!(d.compare_document_position(&b), Some(Greater));
+	assert_eq!(c.compare_document_position(&a), Some(Greater));
+
+	let mut nodes = vec![d.clone(), b.clone(), c.clone(), a.clone()];
+	nodes.sort_by(|x, y| x.compare_document_position(y).unwrap());
+	assert_eq!(nodes, [a.clone(), b, c, d]);
+
+	let other = parse_html().one("<p>");
+	assert_eq!(a.compare_document_position(&other), None);
+}
+
+#[test]
+fn closest() {
+	let html = r"<article><table><tr><td><span>x</span></td></tr></table></article>";
+	let document = parse_html().one(html);
+	let span = document.select_first("span").unwrap().unwrap();
+	let span = span.as_node();
+	let text = span.first_child().unwrap();
+
+	let article = text.closest("article").unwrap().unwrap();
+	assert_eq!(
+		article.as_element().unwrap().name.local,
+		local_name!("article")
+	);
+	assert_eq!(span.closest("span").unwrap().as_ref(), Some(span));
+	assert_eq!(
+		span.closest("tr")
+			.unwrap()
+			.unwrap()
+			.as_element()
+			.unwrap()
+			.name
+			.local,
+		local_name!("tr")
+	);
+	assert!(span.closest("section").unwrap().is_none());
+	assert!(span.closest("<").is_err());
+}
+
+#[test]
+// Hashing a `NodeRef` only uses its address, which interior mutability cannot change.
+#[allow(clippy::mutable_key_type)]
+fn node_identity() {
+	use std::collections::HashSet;
+
+	let document = parse_html().one("<p>a</p><p>a</p>");
+	let paragraphs: Vec<_> = document
+		.select("p")
+		.unwrap()
+		.map(|p| p.as_node().clone())
+		.collect();
+	assert_ne!(paragraphs[0], paragraphs[1]);
+	assert_eq!(paragraphs[0], paragraphs[0].clone());
+
+	let mut visited = HashSet::new();
+	assert!(visited.insert(paragraphs[0].clone()));
+	assert!(visited.insert(paragraphs[1].clone()));
+	assert!(!visited.insert(
+		document
+			.select_first("p")
+			.unwrap()
+			.unwrap()
+			.as_node()
+			.clone()
+	));
+	assert_eq!(visited.len(), 2);
+}
+
+#[test]
+fn node_data_ref_into_node() {
+	let document = parse_html().one("<div><p>a</p></div>");
+	let p = document.select_first("p").unwrap().unwrap();
+	let node = p.clone().into_node();
+	assert_eq!(&node, p.as_node());
+	assert_eq!(NodeRef::from(p), node);
+	node.detach();
+	assert!(document.select_first("p").unwrap().is_none());
+}
+
+#[test]
+fn typed_accessors() {
+	let document = parse_xml().one(r#"<?pi data?><!-- c --><root>text<template/></root>"#);
+	assert!(document.as_document().is_some());
+	let children: Vec<_> = document.children().collect();
+	let pi = children[0].as_processing_instruction().unwrap();
+	assert_eq!(*pi.borrow(), ("pi".to_owned(), "data".to_owned()));
+	assert_eq!(&*children[1].as_comment().unwrap().borrow(), " c ");
+	assert!(children[1].as_text().is_none());
+	let pi = children[0]
+		.clone()
+		.into_processing_instruction_ref()
+		.unwrap();
+	assert_eq!(pi.borrow().1, "data");
+
+	let html = parse_html().one("<!DOCTYPE html><template>x</template>");
+	assert_eq!(
+		html.first_child().unwrap().as_doctype().unwrap().name,
+		"html"
+	);
+	let template = html.select_first("template").unwrap().unwrap();
+	let contents = template.template_contents.as_ref().unwrap();
+	assert!(contents.is_document_fragment());
+	assert!(!template.as_node().is_document_fragment());
+}
+
+#[test]
+fn text_nodes_and_comments() {
+	let document =
+		parse_html().one("<p>Hello <b>HELLO</b></p><!-- hello --><p title=hello>hElLo world</p>");
+	for text in document.text_nodes() {
+		let replaced = {
+			let contents = text.borrow();
+			let lowercase = contents.to_ascii_lowercase();
+			let mut replaced = String::new();
+			let mut last = 0;
+			for (start, _) in lowercase.match_indices("hello") {
+				replaced.push_str(&contents[last..start]);
+				replaced.push_str("bye");
+				last = start + "hello".len();
+			}
+			replaced.push_str(&contents[last..]);
+			replaced
+		};
+		*text.borrow_mut() = replaced;
+	}
+	let body = document.select_first("body").unwrap().unwrap();
+	assert_eq!(
+		body.as_node().inner_html().unwrap(),
+		r#"<p>bye <b>bye</b></p><!-- hello --><p title="hello">bye world</p>"#
+	);
+
+	let comments: Vec<_> = document
+		.comments()
+		.map(|comment| comment.borrow().clone())
+		.collect();
+	assert_eq!(comments, [" hello "]);
+}
+
+#[test]
+fn drop_deep_trees() {
+	let depth = 100_000;
+	let div = || NodeRef::new_element(QualName::new(None, ns!(html), local_name!("div")), vec![]);
+	let root = div();
+	let mut parent = root.clone();
+	for _ in 1..depth {
+		let child = div();
+		parent.append(child.clone());
+		parent = child;
+	}
+	drop(parent);
+	assert_eq!(root.descendants().count(), depth - 1);
+	assert_eq!(root.traverse().count(), 2 * (depth - 1));
+	drop(root);
+
+	let template = || {
+		NodeRef::new_element(
+			QualName::new(None, ns!(html), local_name!("template")),
+			vec![],
+		)
+	};
+	let root = template();
+	let mut parent = root.clone();
+	for _ in 1..depth {
+		let child = template();
+		parent
+			.as_element()
+			.unwrap()
+			.template_contents
+			.as_ref()
+			.unwrap()
+			.append(child.clone());
+		parent = child;
+	}
+	drop(parent);
+	drop(root);
+}
+
+#[test]
+fn clone_tree() {
+	let html = r#"<div class="a b"><p>Text</p><!--c--><template><i>T</i></template></div>"#;
+	let document = parse_html().one(html);
+	let div = document.select_first("div").unwrap().unwrap();
+	let copy = div.as_node().clone_tree();
+	assert!(copy.parent().is_none());
+	assert!(copy.next_sibling().is_none());
+	assert_eq!(copy.to_string(), div.as_node().to_string());
+
+	copy.select_first("p").unwrap().unwrap().as_node().detach();
+	copy.as_element()
+		.unwrap()
+		.attributes
+		.borrow_mut()
+		.class_list()
+		.remove("a");
+	let template = copy.select_first("template").unwrap().unwrap();
+	template.as_node().set_inner_html("<b>U</b>").unwrap();
+	assert!(copy.select_first(".b").unwrap().is_some());
+	assert!(copy.select_first(".a").unwrap().is_none());
+
+	assert_eq!(div.as_node().to_string(), html);
+	assert!(document.select_first("div.a.b > p").unwrap().is_some());
+}
+
+#[test]
+fn deep_eq() {
+	let html = r#"<!DOCTYPE html><p class="a">x<!-- c --></p><template><b>t</b></template>"#;
+	let document = parse_html().one(html);
+	assert!(document.deep_eq(&parse_html().one(html)));
+	assert!(document.deep_eq(&document.clone_tree()));
+
+	let other = parse_html().one(html.replace("<b>t</b>", "<b>u</b>"));
+	assert!(!document.deep_eq(&other));
+	let other = parse_html().one(html.replace("class=\"a\"", "class=\"b\""));
+	assert!(!document.deep_eq(&other));
+	let other = parse_html().one(html.replace("x", "x "));
+	assert!(!document.deep_eq(&other));
+
+	let copy = document.clone_tree();
+	let p = copy.select_first("p").unwrap().unwrap();
+	p.as_node().append(NodeRef::new_text(""));
+	assert!(!document.deep_eq(&copy));
+	copy.normalize();
+	assert!(document.deep_eq(&copy));
+}
+
+#[test]
+fn normalize() {
+	let div = NodeRef::new_element(QualName::new(None, ns!(html), local_name!("div")), None);
+	div.append(NodeRef::new_text(""));
+	div.append(NodeRef::new_text("a"));
+	div.append(NodeRef::new_text("b"));
+	div.append(NodeRef::new_text("c"));
+	div.append(NodeRef::new_comment("x"));
+	div.append(NodeRef::new_text(""));
+	let span = NodeRef::new_element(QualName::new(None, ns!(html), local_name!("span")), None);
+	span.append(NodeRef::new_text("d"));
+	span.append(NodeRef::new_text("e"));
+	div.append(span.clone());
+	div.append(NodeRef::new_text("f"));
+
+	div.normalize();
+	let children = div.children().collect::<Vec<_>>();
+	assert_eq!(children.len(), 4);
+	assert_eq!(&*children[0].as_text().unwrap().borrow(), "abc");
+	assert!(children[1].as_comment().is_some());
+	assert_eq!(children[2], span);
+	assert_eq!(&*children[3].as_text().unwrap().borrow(), "f");
+	assert_eq!(children[3].previous_sibling().unwrap(), span);
+	assert_eq!(span.children().count(), 1);
+	assert_eq!(div.to_string(), "<div>abc<!--x--><span>de</span>f</div>");
+}
+
+#[test]
+fn try_detach() {
+	let document = parse_html().one("<p>a</p><p>b</p>");
+	let p = document.select_first("p").unwrap().unwrap();
+	let p = p.as_node();
+	assert!(p.try_detach());
+	assert!(p.parent().is_none());
+	assert!(!p.try_detach());
+	assert_eq!(document.select("p").unwrap().count(), 1);
+}
+
+#[test]
+fn bulk_detach_and_replace() {
+	let document =
+		parse_html().one("<div><script>a</script><p>1</p><script>b</script><p>2</p><p>3</p></div>");
+	let removed = document.select("script").unwrap().detach_all();
+	assert_eq!(removed.len(), 2);
+	assert!(removed
+		.iter()
+		.all(|script| script.as_node().parent().is_none()));
+
+	let replaced = document.select("p").unwrap().replace_all_with(|p| {
+		let em = NodeRef::new_element(QualName::new(None, ns!(html), local_name!("em")), vec![]);
+		em.append(NodeRef::new_text(p.text_contents()));
+		em
+	});
+	assert_eq!(replaced.len(), 3);
+	let div = document.select_first("div").unwrap().unwrap();
+	assert_eq!(
+		div.as_node().inner_html().unwrap(),
+		"<em>1</em><em>2</em><em>3</em>"
+	);
+}
+
+#[test]
+fn replace_with() {
+	let document = parse_html().one("<div><a></a><b></b><i></i></div><p><span></span></p>");
+	let div = document.select_first("div").unwrap().unwrap();
+	let div = div.as_node();
+	let find = |selector| {
+		document
+			.select_first(selector)
+			.unwrap()
+			.unwrap()
+			.as_node()
+			.clone()
+	};
+
+	find("a").replace_with(NodeRef::new_text("1"));
+	find("i").replace_with(NodeRef::new_text("3"));
+	find("b").replace_with(find("span"));
+	assert_eq!(div.to_string(), "<div>1<span></span>3</div>");
+	assert_eq!(find("p").to_string(), "<p></p>");
+	assert_eq!(
+		div.first_child().unwrap().next_sibling().unwrap(),
+		find("span")
+	);
+	assert_eq!(
+		div.last_child().unwrap().previous_sibling().unwrap(),
+		find("span")
+	);
+
+	// Replacing an only child.
+	let p = find("p");
+	p.append(NodeRef::new_text("old"));
+	p.first_child()
+		.unwrap()
+		.replace_with(NodeRef::new_text("new"));
+	assert_eq!(p.to_string(), "<p>new</p>");
+	assert_eq!(p.first_child(), p.last_child());
+
+	// Replacing a node without a parent does nothing.
+	let orphan = NodeRef::new_text("orphan");
+	let replacement = NodeRef::new_text("replacement");
+	orphan.replace_with(replacement.clone());
+	assert!(replacement.previous_sibling().is_none());
+	assert!(orphan.next_sibling().is_none());
+}
+
+#[test]
+fn wrap_and_unwrap() {
+	let document = parse_html().one("<div><a></a><b>1<i></i>2</b></div>");
+	let div = document.select_first("div").unwrap().unwrap();
+	let div = div.as_node();
+	let find = |selector| {
+		document
+			.select_first(selector)
+			.unwrap()
+			.unwrap()
+			.as_node()
+			.clone()
+	};
+	let new_element = |name| NodeRef::new_element(QualName::new(None, ns!(html), name), None);
+
+	find("a").wrap(new_element(local_name!("p")));
+	find("b").wrap(new_element(local_name!("span")));
+	assert_eq!(
+		div.to_string(),
+		"<div><p><a></a></p><span><b>1<i></i>2</b></span></div>"
+	);
+	assert_eq!(div.last_child().unwrap(), find("span"));
+
+	find("b").unwrap();
+	assert_eq!(
+		div.to_string(),
+		"<div><p><a></a></p><span>1<i></i>2</span></div>"
+	);
+	find("p").unwrap();
+	assert_eq!(div.to_string(), "<div><a></a><span>1<i></i>2</span></div>");
+	assert_eq!(div.first_child().unwrap(), find("a"));
+	assert!(find("a").previous_sibling().is_none());
+
+	let orphan = new_element(local_name!("b"));
+	orphan.append(NodeRef::new_text("x"));
+	orphan.unwrap();
+	assert_eq!(orphan.to_string(), "<b>x</b>");
+	let wrapper = new_element(local_name!("i"));
+	orphan.wrap(wrapper.clone());
+	assert_eq!(orphan.parent().unwrap(), wrapper);
+}
+
+#[test]
+fn select() {
+	let html = r"
+<title>Test case</title>
+<p class=foo>Foo
+<p>Bar
+<p class=foo>Foo
+";
+
+	let document = parse_html().one(html);
+	let matching = document.select("p.foo").unwrap().collect::<Vec<_>>();
+	assert_eq!(matching.len(), 2);
+	let child = matching[0].as_node().first_child().unwrap();
+	assert_eq!(&**child.as_text().unwrap().borrow(), "Foo\n");
+	assert_eq!(matching[0].attributes.borrow().get("class"), Some("foo"));
+	assert_eq!(
+		matching[0].attributes.borrow().get(local_name!("class")),
+		Some("foo")
+	);
+
+	let selectors = Selectors::compile("p.foo").unwrap();
+	let matching2 = selectors
+		.filter(document.descendants().elements())
+		.collect::<Vec<_>>();
+	assert_eq!(matching, matching2);
+}
+
+#[test]
+fn select_first() {
+	let html = r"
+<title>Test case</title>
+<p class=foo>Foo
+<p>Bar
+<p class=foo>Baz
+";
+
+	let document = parse_html().one(html);
+	let matching = document.select_first("p.foo").unwrap().unwrap();
+	let child = matching.as_node().first_child().unwrap();
+	assert_eq!(&**child.as_text().unwrap().borrow(), "Foo\n");
+	assert_eq!(matching.attributes.borrow().get("class"), Some("foo"));
+	assert_eq!(
+		matching.attributes.borrow().get(local_name!("class")),
+		Some("foo")
+	);
+
+	assert!(document.select_first("p.bar").unwrap().is_none());
+	assert!(document.select_first("p..bar").is_err());
+
+	let first = document.descendants().select_first("p").unwrap().unwrap();
+	assert_eq!(first, matching);
+}
+
+#[test]
+fn selectors_to_string() {
+	let selectors = Selectors::compile("div , p").unwrap();
+	assert_eq!(selectors.to_string(), "div, p");
+	assert_eq!(selectors.0[1].to_string(), "p");
+
+	let normalized = Selectors::compile("ul>li.a , a[href]").unwrap().to_string();
+	assert_eq!(normalized, "ul > li.a, a[href]");
+	assert_eq!(
+		Selectors::compile(&normalized).unwrap().to_string(),
+		normalized
+	);
+
+	assert_eq!(Selectors(vec![]).to_string(), "");
+}
+
+#[test]
+fn matching_selectors() {
+	let document = parse_html().one(r#"<p id="x" class="a">text</p>"#);
+	let p = document.get_element_by_id("x").unwrap();
+	let selectors = Selectors::compile("#x, div, p, p.a").unwrap();
+	let mut matching = p.matching_selectors(&selectors);
+	assert_eq!(
+		matching
+			.iter()
+			.map(|selector| selector.to_string())
+			.collect::<Vec<_>>(),
+		["#x", "p", "p.a"]
+	);
+	matching.sort_by_key(|selector| selector.specificity());
+	assert_eq!(matching.last().unwrap().to_string(), "#x");
+	assert!(matching[0].specificity() < matching[1].specificity());
+
+	let text = p.first_child().unwrap();
+	assert!(text.matching_selectors(&selectors).is_empty());
+}
+
+#[test]
+fn match_stylesheet() {
+	let document =
+		parse_html().one(r#"<div><p id="x" class="a">1</p><p>2</p><span>3</span></div>"#);
+	let rules: Vec<_> = [
+		("#x", "color: red"),
+		("p", "margin: 0"),
+		("div p, .a", "color: blue"),
+		("p", "padding: 0"),
+	]
+	.iter()
+	.map(|&(selectors, declarations)| (Selectors::compile(selectors).unwrap(), declarations))
+	.collect();
+
+	let matches = document.match_stylesheet(&rules);
+	let matches: Vec<_> = matches
+		.iter()
+		.map(|(node, declarations)| {
+			let declarations: Vec<&str> = declarations.iter().map(|d| **d).collect();
+			(node.text_contents(), declarations)
+		})
+		.collect();
+	assert_eq!(
+		matches,
+		[
+			(
+				"1".to_owned(),
+				vec!["margin: 0", "padding: 0", "color: blue", "color: red"]
+			),
+			(
+				"2".to_owned(),
+				vec!["margin: 0", "padding: 0", "color: blue"]
+			),
+		]
+	);
+}
+
+#[test]
+fn select_one() {
+	let document = parse_html().one(r#"<p id="a">1</p><p>2</p>"#);
+	assert_eq!(document.select_one("#a").unwrap().text_contents(), "1");
+
+	let error = document.select_one("span").err().unwrap();
+	assert_eq!(
+		error,
+		SelectError::NoMatch {
+			selector: "span".to_owned()
+		}
+	);
+	assert_eq!(error.to_string(), "no element matches `span`");
+
+	let error = document.select_one("p").err().unwrap();
+	assert_eq!(error.to_string(), "more than one element matches `p`");
+
+	match document.select_one("p >").err().unwrap() {
+		SelectError::InvalidSelector(error) => assert_eq!(error.column, 4),
+		error => panic!("unexpected error: {}", error),
+	}
+}
+
+#[test]
+fn selector_error() {
+	let error = Selectors::compile("div >>> span").unwrap_err();
+	assert_eq!(error.line, 1);
+	assert_eq!(error.column, 6);
+	assert_eq!(
+		error.to_string(),
+		"combinator not followed by a selector at line 1, column 6"
+	);
+
+	let document = parse_html().one("<p>");
+	let error = document.select("p:frobnicate").err().unwrap();
+	assert!(error.message.contains("frobnicate"), "{}", error);
+}
+
+#[test]
+fn to_string() {
+	let html = r"<!DOCTYPE html>
+<html>
+    <head>
+        <title>Test case</title>
+    </head>
+    <body>
+        <p class=foo>Foo
+    </body>
+</html>";
+
+	let document = parse_html().one(html);
+	assert_eq!(
+		document
+			.inclusive_descendants()
+			.nth(11)
+			.unwrap()
+			.to_string(),
+		"<p class=\"foo\">Foo\n    \n</p>"
+	);
+}
+
+#[test]
+fn specificity() {
+	let selectors = Selectors::compile(".example, :first-child, div").unwrap();
+	let specificities = selectors
+		.0
+		.iter()
+		.map(|s| s.specificity())
+		.collect::<Vec<_>>();
+	assert_eq!(specificities.len(), 3);
+	assert!(specificities[0] == specificities[1]);
+	assert!(specificities[0] > specificities[2]);
 	assert!(specificities[1] > specificities[2]);
 }
+
+#[test]
+fn element_builder() {
+	let list = NodeRef::element("ul")
+		.attr("id", "menu")
+		.class("nav")
+		.attr("role", "list")
+		.class("main")
+		.attr("id", "top")
+		.children(
+			["One", "Two"]
+				.iter()
+				.map(|&label| NodeRef::element("li").text(label)),
+		)
+		.child(NodeRef::new_comment("end"))
+		.build();
+	assert_eq!(
+		list.to_string(),
+		r#"<ul id="top" class="nav main" role="list"><li>One</li><li>Two</li><!--end--></ul>"#
+	);
+	let element = list.as_element().unwrap();
+	assert_eq!(element.name.ns, ns!(html));
+	assert!(element.attributes.borrow().class_cache.is_some());
+	assert_eq!(list.select("ul.nav.main > li").unwrap().count(), 2);
+
+	let template = NodeRef::element("template").text("hidden").build();
+	assert_eq!(template.children().count(), 0);
+	assert_eq!(template.to_string(), "<template>hidden</template>");
+}
+
+#[test]
+fn create_nodes() {
+	let document = parse_html().one("<p>Hello</p>");
+	let body = document.select_first("body").unwrap().unwrap();
+	let element = document.create_element("DIV");
+	assert!(element.parent().is_none());
+	element.append(document.create_text_node("World"));
+	element.append(document.create_comment("note"));
+	body.as_node().append(element.clone());
+	assert_eq!(
+		element.as_element().unwrap().name,
+		QualName::new(None, ns!(html), local_name!("div"))
+	);
+	assert_eq!(
+		body.as_node().to_string(),
+		"<body><p>Hello</p><div>World<!--note--></div></body>"
+	);
+	assert_eq!(document.select("div").unwrap().count(), 1);
+}
+
+#[test]
+fn html() {
+	let document = parse_html().one("<p class=x>Hello <b>world</b></p>");
+	let p = document.select_first("p").unwrap().unwrap();
+	assert_eq!(p.as_node().html(), r#"<p class="x">Hello <b>world</b></p>"#);
+	assert_eq!(
+		p.as_node().html(),
+		p.as_node().serialize_to_string().unwrap()
+	);
+	assert_eq!(p.as_node().inner_html().unwrap(), "Hello <b>world</b>");
+}
+
+#[test]
+fn trimmed_text() {
+	let document = parse_html().one("<div>\n  Hello,\t<b> big </b>\n\nwo<i>rld</i>\u{a0}! </div>");
+	let div = document.select_first("div").unwrap().unwrap();
+	assert_eq!(div.as_node().trimmed_text(), "Hello, big world\u{a0}!");
+	assert_eq!(NodeRef::new_text(" \n ").trimmed_text(), "");
+}
+
+#[test]
+fn select_scope() {
+	let document =
+		parse_html().one("<ul id=outer><li>1<ul><li>1.1</li></ul></li><li>2</li></ul><li>3</li>");
+	let outer = document.select_first("#outer").unwrap().unwrap();
+	let texts = |node: &NodeRef, selector: &str| {
+		node.select(selector)
+			.unwrap()
+			.map(|li| li.text_contents())
+			.collect::<Vec<_>>()
+	};
+	assert_eq!(texts(outer.as_node(), ":scope > li"), ["11.1", "2"]);
+	assert_eq!(texts(outer.as_node(), ":scope li li"), ["1.1"]);
+	assert_eq!(texts(outer.as_node(), ":scope"), ["11.12"]);
+	let selectors = Selectors::compile(":scope > li").unwrap();
+	assert_eq!(outer.as_node().select_compiled(&selectors).count(), 2);
+
+	// Without an element to scope to, `:scope` is the root element.
+	assert_eq!(texts(&document, ":scope > body > li"), ["3"]);
+	assert_eq!(document.select(":scope").unwrap().count(), 1);
+}
+
+#[test]
+fn has_pseudo_class() {
+	let document = parse_html().one(
+		"<article id=a><p><img></p></article>\
+		 <article id=b><p>text</p></article>\
+		 <article id=c><img></article>\
+		 <h2 id=d></h2><p>after d</p>\
+		 <h2 id=e></h2><div><p>inside</p></div>",
+	);
+	let ids = |selector: &str| {
+		document
+			.select(selector)
+			.unwrap()
+			.map(|element| element.attributes.borrow().get("id").unwrap().to_owned())
+			.collect::<Vec<_>>()
+	};
+	assert_eq!(ids("article:has(img)"), ["a", "c"]);
+	assert_eq!(ids("article:has(> img)"), ["c"]);
+	assert_eq!(ids("article:has(p img, > img)"), ["a", "c"]);
+	assert_eq!(ids("article:not(:has(img))"), ["b"]);
+	assert_eq!(ids("article:has(p:has(img))"), ["a"]);
+	assert_eq!(ids("h2:has(+ p)"), ["d"]);
+	assert_eq!(ids("h2:has(~ div p)"), ["d", "e"]);
+	// The whole relative selector must match after the anchor.
+	assert_eq!(ids("article:has(body img)").len(), 0);
+
+	let selectors = Selectors::compile("article:has(>img,p  ~  b:has( i ))").unwrap();
+	assert_eq!(selectors.to_string(), "article:has(> img, p ~ b:has(i))");
+
+	// `:has()` is as specific as its most specific argument.
+	let specificity = |css| Selectors::compile(css).unwrap().0[0].specificity();
+	assert_eq!(specificity("a:has(#x, b)"), specificity("a#x"));
+	assert_eq!(specificity("a:has(> b)"), specificity("a b"));
+	assert_eq!(specificity("a:not(:has(.x .y))"), specificity("a.x.y"));
+	assert_eq!(specificity("a:has(b:has(#x))"), specificity("a b#x"));
+	assert!(Selectors::compile("article:has()").is_err());
+	assert!(Selectors::compile("article:has(>)").is_err());
+}
+
+#[test]
+fn select_with_opts() {
+	let document = parse_html()
+		.one("<p class=Intro id=First data-kind=TEXT>1</p><p class=intro><b class=NOTE></b></p>");
+	let insensitive = MatchOpts::new().case_sensitivity(CaseSensitivity::AsciiCaseInsensitive);
+	let count = |selector: &str, opts: MatchOpts| {
+		document.select_with_opts(selector, opts).unwrap().count()
+	};
+	let select = document.select_with_opts("p", insensitive).unwrap();
+	assert_eq!(select.opts(), &insensitive);
+	assert!(select.scope().is_none());
+	assert_eq!(count(".intro", MatchOpts::default()), 1);
+	assert_eq!(count(".intro", insensitive), 2);
+	assert_eq!(count("#first", MatchOpts::default()), 0);
+	assert_eq!(count("#first", insensitive), 1);
+	assert_eq!(count("p:has(.note)", insensitive), 1);
+	// Attribute selectors are compared case-insensitively too, or as their flag says by default.
+	assert_eq!(count("[data-kind=text]", insensitive), 1);
+	assert_eq!(count("[data-kind^=te]", insensitive), 1);
+	assert_eq!(count("p:has([class=note])", insensitive), 1);
+	assert_eq!(count("[data-kind=text]", MatchOpts::default()), 0);
+	assert_eq!(count("[data-kind=text i]", MatchOpts::default()), 1);
+	let input = parse_html().one("<input type=text name=q>");
+	let count = |selector, opts| input.select_with_opts(selector, opts).unwrap().count();
+	assert_eq!(count("[type=TEXT]", insensitive), 1);
+	assert_eq!(count("[name=Q]", insensitive), 1);
+	assert_eq!(count("[name=Q]", MatchOpts::default()), 0);
+}
+
+#[test]
+fn quirks_mode() {
+	let quirky = parse_html().one("<p>No doctype");
+	assert_eq!(quirky.quirks_mode(), Some(QuirksMode::Quirks));
+	let p = quirky.select_first("p").unwrap().unwrap();
+	assert_eq!(p.as_node().quirks_mode(), Some(QuirksMode::Quirks));
+
+	let standard = parse_html().one("<!doctype html><p>Doctype");
+	assert_eq!(standard.quirks_mode(), Some(QuirksMode::NoQuirks));
+	let limited = parse_html().one(
+		r#"<!DOCTYPE html PUBLIC "-//W3C//DTD XHTML 1.0 Transitional//EN" "http://www.w3.org/TR/xhtml1/DTD/xhtml1-transitional.dtd"><p>"#,
+	);
+	assert_eq!(limited.quirks_mode(), Some(QuirksMode::LimitedQuirks));
+
+	p.as_node().detach();
+	assert_eq!(p.as_node().quirks_mode(), None);
+}
+
+#[test]
+fn document_encoding() {
+	let document = parse_html_from_bytes(b"<meta charset=shift_jis><p>\x93\xfa", None);
+	assert_eq!(document.document_encoding(), Some(encoding_rs::SHIFT_JIS));
+	let p = document.select_first("p").unwrap().unwrap();
+	assert_eq!(
+		p.as_node().document_encoding(),
+		Some(encoding_rs::SHIFT_JIS)
+	);
+	assert_eq!(
+		parse_html_from_bytes(b"\xef\xbb\xbf<p>", Some(encoding_rs::SHIFT_JIS)).document_encoding(),
+		Some(encoding_rs::UTF_8)
+	);
+	assert_eq!(
+		parse_html_from_bytes(b"<p>caf\xe9", None).document_encoding(),
+		Some(encoding_rs::WINDOWS_1252)
+	);
+	assert_eq!(parse_html().one("<p>").document_encoding(), None);
+}
+
+#[test]
+fn serialize_into_vec() {
+	let document = parse_html().one("<p class=a>Hello <b>world</b></p><!--note-->");
+	let p = document.select_first("p").unwrap().unwrap();
+	let mut buf = b"stale contents".to_vec();
+	p.as_node().serialize_into_vec(&mut buf).unwrap();
+	assert_eq!(buf, br#"<p class="a">Hello <b>world</b></p>"#);
+
+	document.serialize_into_vec(&mut buf).unwrap();
+	assert_eq!(buf, document.to_string().as_bytes());
+	let capacity = buf.capacity();
+	document.serialize_into_vec(&mut buf).unwrap();
+	assert_eq!(buf.capacity(), capacity);
+}
+
+#[test]
+fn select_count() {
+	let document = parse_html().one("<ul><li>1</li><li class=x>2</li><li class=x>3</li></ul>");
+	assert_eq!(document.select_count("li").unwrap(), 3);
+	assert_eq!(document.select_count("li.x").unwrap(), 2);
+	assert_eq!(document.select_count("ol").unwrap(), 0);
+	let ul = document.select_first("ul").unwrap().unwrap();
+	assert_eq!(ul.as_node().select_count(":scope > li:not(.x)").unwrap(), 1);
+	assert!(document.select_count("li >").is_err());
+}
+
+#[test]
+fn select_first_stops_at_first_match() {
+	let html = format!("<p id=target></p>{}", "<div><p></p></div>".repeat(10_000));
+	let document = parse_html().one(html);
+	let mut visited = 0;
+	let first = document
+		.inclusive_descendants()
+		.inspect(|_| visited += 1)
+		.select_first("p")
+		.unwrap()
+		.unwrap();
+	assert_eq!(first.attributes.borrow().get("id"), Some("target"));
+	// The document, `<html>`, `<head>`, `<body>` and the first `<p>`.
+	assert_eq!(visited, 5);
+
+	let first = document.select_first("p").unwrap().unwrap();
+	assert_eq!(first.attributes.borrow().get("id"), Some("target"));
+}
+
+#[test]
+fn attribute_tokens() {
+	let document =
+		parse_html().one("<a rel=' nofollow\tnoopener\n noopener ' title=' Home  page '></a>");
+	let a = document.select_first("a").unwrap().unwrap();
+	let attributes = a.attributes.borrow();
+	assert_eq!(
+		attributes.tokens("rel").collect::<Vec<_>>(),
+		["nofollow", "noopener", "noopener"]
+	);
+	assert_eq!(attributes.tokens("class").count(), 0);
+	assert_eq!(attributes.get_trimmed("title"), Some("Home  page"));
+	assert_eq!(
+		attributes.get_trimmed("rel"),
+		Some("nofollow\tnoopener\n noopener")
+	);
+	assert_eq!(attributes.get_trimmed("href"), None);
+}
+
+#[test]
+fn toggle_attribute() {
+	let document = parse_html().one("<input disabled=disabled class=a>");
+	let input = document.select_first("input").unwrap().unwrap();
+	let mut attributes = input.attributes.borrow_mut();
+	assert!(!attributes.toggle("disabled", None));
+	assert!(!attributes.contains("disabled"));
+	assert!(attributes.toggle("disabled", None));
+	assert_eq!(attributes.get("disabled"), Some(""));
+	assert!(attributes.toggle("hidden", Some(true)));
+	assert!(attributes.toggle("hidden", Some(true)));
+	assert!(!attributes.toggle("checked", Some(false)));
+	assert!(!attributes.contains("checked"));
+
+	attributes.insert("disabled", "yes".to_owned());
+	assert!(attributes.toggle("disabled", Some(true)));
+	assert_eq!(attributes.get("disabled"), Some("yes"));
+
+	assert!(!attributes.toggle("class", None));
+	drop(attributes);
+	assert_eq!(document.select_count(".a").unwrap(), 0);
+	assert!(input.attributes.borrow_mut().toggle("class", None));
+	assert_eq!(document.select_count("[class]").unwrap(), 1);
+	assert_eq!(document.select_count(".a").unwrap(), 0);
+}
+
+#[test]
+fn merge_attributes() {
+	let defaults = NodeRef::element("a")
+		.attr("href", "#")
+		.attr("class", "link")
+		.attr("rel", "nofollow")
+		.build();
+	let link = NodeRef::element("a")
+		.attr("title", "Home")
+		.attr("rel", "noopener")
+		.build();
+	let element = link.as_element().unwrap();
+	element
+		.attributes
+		.borrow_mut()
+		.merge_from(&defaults.as_element().unwrap().attributes.borrow());
+	assert_eq!(
+		link.to_string(),
+		r##"<a title="Home" rel="nofollow" href="#" class="link"></a>"##
+	);
+	assert_eq!(link.select_count(".link").unwrap(), 1);
+
+	element.attributes.borrow_mut().extend(vec![
+		(
+			ExpandedName::new(ns!(), local_name!("class")),
+			crate::Attribute {
+				prefix: None,
+				value: "button".to_owned(),
+			},
+		),
+		(
+			ExpandedName::new(ns!(), local_name!("target")),
+			crate::Attribute {
+				prefix: None,
+				value: "_blank".to_owned(),
+			},
+		),
+	]);
+	assert_eq!(
+		link.to_string(),
+		r##"<a title="Home" rel="nofollow" href="#" class="button" target="_blank"></a>"##
+	);
+	assert_eq!(link.select_count(".link").unwrap(), 0);
+	assert_eq!(link.select_count(".button").unwrap(), 1);
+}
+
+#[test]
+fn collect_attributes() {
+	let attribute = |local: &str, value: &str| {
+		(
+			ExpandedName::new(ns!(), local),
+			crate::Attribute {
+				prefix: None,
+				value: value.to_owned(),
+			},
+		)
+	};
+	let mut attributes: crate::Attributes =
+		vec![attribute("id", "main"), attribute("class", "a b")]
+			.into_iter()
+			.collect();
+	assert_eq!(attributes.get("id"), Some("main"));
+	assert!(attributes.class_cache.is_some());
+	attributes.extend(vec![attribute("class", "c")]);
+	assert_eq!(attributes.get("class"), Some("c"));
+	assert!(attributes.has_class(b"c", CaseSensitivity::CaseSensitive));
+	assert!(!attributes.has_class(b"a", CaseSensitivity::CaseSensitive));
+}
+
+#[test]
+fn construct_attributes() {
+	let attributes = crate::Attributes::from_local_names(vec![("id", "main"), ("class", "a b")]);
+	assert_eq!(
+		attributes
+			.iter()
+			.map(|(name, _)| &*name.local)
+			.collect::<Vec<_>>(),
+		["id", "class"]
+	);
+	assert!(attributes.has_class(b"b", CaseSensitivity::CaseSensitive));
+	assert!(crate::Attributes::default().is_empty());
+
+	let element = NodeRef::new_element(
+		QualName::new(None, ns!(html), local_name!("p")),
+		attributes.map,
+	);
+	assert_eq!(element.to_string(), r#"<p id="main" class="a b"></p>"#);
+	assert_eq!(element.select_count("p.a").unwrap(), 1);
+}
+
+#[test]
+fn local_and_tag_names() {
+	let document = parse_xml().one(
+		r#"<root xmlns:svg="http://www.w3.org/2000/svg" xmlns:h="http://www.w3.org/1999/xhtml"><svg:rect/><h:P/><item/></root>"#,
+	);
+	let names = document
+		.descendants()
+		.map(|node| {
+			(
+				node.local_name().map(|name| name.to_string()),
+				node.tag_name(),
+			)
+		})
+		.collect::<Vec<_>>();
+	let expected = |local: &str, tag: &str| (Some(local.to_owned()), Some(tag.to_owned()));
+	assert_eq!(
+		names,
+		[
+			expected("root", "root"),
+			expected("rect", "svg:rect"),
+			expected("P", "p"),
+			expected("item", "item"),
+		]
+	);
+
+	let html = parse_html().one("<p>text</p>");
+	let p = html.select_first("p").unwrap().unwrap();
+	assert_eq!(p.as_node().local_name(), Some(&local_name!("p")));
+	assert_eq!(p.as_node().first_child().unwrap().local_name(), None);
+	assert_eq!(html.tag_name(), None);
+}
+
+#[test]
+fn id_and_classes() {
+	let document = parse_html().one("<p id=intro class=' lead\tbig\nlead '></p><p></p>");
+	let mut paragraphs = document.select("p").unwrap();
+	let first = paragraphs.next().unwrap();
+	assert_eq!(first.id().as_deref(), Some("intro"));
+	assert_eq!(first.classes(), ["lead", "big", "lead"]);
+	let second = paragraphs.next().unwrap();
+	assert_eq!(second.id(), None);
+	assert!(second.classes().is_empty());
+
+	// A single class matches whatever whitespace surrounds it, as `classes` returns it.
+	let document = parse_html().one("<p class=' solo	'></p><p class=\"\n\"></p>");
+	let solo = document.select_first(".solo").unwrap().unwrap();
+	assert_eq!(solo.classes(), ["solo"]);
+	assert_eq!(document.select("p[class]").unwrap().count(), 2);
+	assert_eq!(document.select(".solo, .\\a").unwrap().count(), 1);
+}
+
+#[test]
+fn descendants_with_depth() {
+	let document = parse_html().one("<div><p><b>x</b></p><p>y</p></div>");
+	let div = document.select_first("div").unwrap().unwrap();
+	let depths = div
+		.as_node()
+		.descendants_with_depth()
+		.map(|(node, depth)| {
+			(
+				node.tag_name().unwrap_or_else(|| node.text_contents()),
+				depth,
+			)
+		})
+		.collect::<Vec<_>>();
+	let expected = [("p", 1), ("b", 2), ("x", 3), ("p", 1), ("y", 2)];
+	assert_eq!(
+		depths,
+		expected
+			.iter()
+			.map(|&(name, depth)| (name.to_owned(), depth))
+			.collect::<Vec<_>>()
+	);
+	assert_eq!(
+		NodeRef::new_text("leaf").descendants_with_depth().count(),
+		0
+	);
+}
+
+#[test]
+fn traverse_edges() {
+	use crate::iter::NodeEdge;
+
+	let document = parse_html().one("<ul><li>One</li><li><b>Two</b></li></ul>");
+	let ul = document.select_first("ul").unwrap().unwrap();
+	let mut outline = String::new();
+	let mut depth = 0;
+	for edge in ul.as_node().traverse_inclusive() {
+		match edge {
+			NodeEdge::Start(node) => {
+				let label = node.tag_name().unwrap_or_else(|| node.text_contents());
+				outline.push_str(&format!("{}{}\n", "  ".repeat(depth), label));
+				depth += 1;
+			}
+			NodeEdge::End(_) => depth -= 1,
+		}
+	}
+	assert_eq!(outline, "ul\n  li\n    One\n  li\n    b\n      Two\n");
+	assert_eq!(depth, 0);
+
+	// Every node, including leaves, has both a start and an end edge.
+	let edges = ul.as_node().traverse().collect::<Vec<_>>();
+	assert_eq!(edges.len(), 2 * ul.as_node().descendants().count());
+	assert!(matches!(&edges[1], NodeEdge::Start(node) if node.as_text().is_some()));
+	assert!(matches!(&edges[2], NodeEdge::End(node) if node.as_text().is_some()));
+}
+
+#[test]
+fn map_tree() {
+	let document = parse_html().one(
+		"<div><script>alert(1)</script><p>Keep <i>this</i></p><template><script></script>t</template></div>",
+	);
+	let div = document.select_first("div").unwrap().unwrap();
+	let mut visited = Vec::new();
+	let mapped = div.as_node().map_tree(|node| {
+		visited.push(node.tag_name().unwrap_or_else(|| node.text_contents()));
+		match node.local_name() {
+			Some(&local_name!("script")) => Some(NodeRef::new_comment("removed")),
+			Some(&local_name!("i")) => Some(NodeRef::element("em").text("that").build()),
+			_ => None,
+		}
+	});
+	assert_eq!(
+		mapped.to_string(),
+		"<div><!--removed--><p>Keep <em>that</em></p><template><!--removed-->t</template></div>"
+	);
+	// Replaced nodes are not descended into.
+	assert_eq!(
+		visited,
+		["div", "script", "p", "Keep ", "i", "template", "script", "t"]
+	);
+	// The original is unchanged.
+	assert_eq!(
+		div.as_node().to_string(),
+		"<div><script>alert(1)</script><p>Keep <i>this</i></p><template><script></script>t</template></div>"
+	);
+
+	// Returning a node of the original tree copies it instead of moving it.
+	let original = div.as_node().to_string();
+	let p = div
+		.as_node()
+		.select_first("p")
+		.unwrap()
+		.unwrap()
+		.as_node()
+		.clone();
+	let mapped = div.as_node().map_tree(|node| match node.local_name() {
+		Some(&local_name!("script")) => Some(p.clone()),
+		_ => None,
+	});
+	assert_eq!(
+		mapped.to_string(),
+		"<div><p>Keep <i>this</i></p><p>Keep <i>this</i></p><template><p>Keep <i>this</i></p>t</template></div>"
+	);
+	assert_eq!(div.as_node().to_string(), original);
+	assert_eq!(p.parent().unwrap(), *div.as_node());
+	let whole = div.as_node().map_tree(|node| Some(node.clone()));
+	assert!(whole.deep_eq(div.as_node()) && whole != *div.as_node());
+	assert_eq!(div.as_node().to_string(), original);
+	let detached = NodeRef::new_text("detached");
+	let copy = detached.map_tree(|node| Some(node.clone()));
+	assert!(copy.deep_eq(&detached) && copy != detached);
+
+	let replaced = div.as_node().map_tree(|_| Some(NodeRef::new_text("root")));
+	assert_eq!(replaced.to_string(), "root");
+	assert!(div.as_node().map_tree(|_| None).deep_eq(div.as_node()));
+}
+
+#[test]
+fn serialize_with_bom_and_trailing_newline() {
+	let document = parse_html().one("<p>Hello</p>");
+	let p = document.select_first("p").unwrap().unwrap();
+	let serialize = |node: &NodeRef, opts: &HtmlSerializeOpts| {
+		let mut bytes = Vec::new();
+		node.serialize_with_opts(&mut bytes, opts).unwrap();
+		bytes
+	};
+	assert_eq!(
+		serialize(p.as_node(), &HtmlSerializeOpts::default()),
+		b"<p>Hello</p>"
+	);
+	let opts = HtmlSerializeOpts {
+		byte_order_mark: true,
+		trailing_newline: true,
+		..Default::default()
+	};
+	assert_eq!(serialize(p.as_node(), &opts), b"\xef\xbb\xbf<p>Hello</p>\n");
+
+	let trailing_newline = HtmlSerializeOpts {
+		trailing_newline: true,
+		..Default::default()
+	};
+	let text = NodeRef::new_text("line\n");
+	assert_eq!(serialize(&text, &trailing_newline), b"line\n");
+	let mut bytes = Vec::new();
+	p.as_node()
+		.serialize_inner_with_opts(&mut bytes, &trailing_newline)
+		.unwrap();
+	assert_eq!(bytes, b"Hello\n");
+
+	let tempdir = TempDir::new().unwrap();
+	let path = tempdir.path().join("bom.html");
+	document.serialize_to_file_with_opts(&path, &opts).unwrap();
+	let written = std::fs::read(&path).unwrap();
+	assert!(written.starts_with(b"\xef\xbb\xbf<html>"));
+	assert!(written.ends_with(b"</html>\n"));
+	let reparsed = parse_html_from_bytes(&written, None);
+	assert_eq!(reparsed.document_encoding(), Some(encoding_rs::UTF_8));
+	assert_eq!(
+		reparsed.select_first("p").unwrap().unwrap().text_contents(),
+		"Hello"
+	);
+}
+
+#[test]
+fn fragment_parser() {
+	let parser = crate::FragmentParser::new(QualName::new(None, ns!(html), local_name!("tbody")));
+	let rows = ["<tr><td>1", "<tr><td>2</td></tr><tr><td>3"]
+		.iter()
+		.map(|html| parser.parse(html))
+		.collect::<Vec<_>>();
+	assert!(rows[0].is_document_fragment());
+	assert!(rows[0].parent().is_none());
+	assert_eq!(
+		rows[0]
+			.first_child()
+			.unwrap()
+			.as_element()
+			.unwrap()
+			.name
+			.local
+			.as_ref(),
+		"tr"
+	);
+	assert_eq!(rows[0].to_string(), "<tr><td>1</td></tr>");
+	assert_eq!(rows[1].children().count(), 2);
+	assert_eq!(rows[1].select_count("td").unwrap(), 2);
+
+	let errors = std::rc::Rc::new(std::cell::Cell::new(0));
+	let counter = errors.clone();
+	let parser = crate::FragmentParser::with_options(
+		crate::ParseOpts {
+			on_parse_error: Some(Box::new(move |_| counter.set(counter.get() + 1))),
+			..Default::default()
+		},
+		QualName::new(None, ns!(html), local_name!("div")),
+		vec![],
+	);
+	assert_eq!(parser.parse("<p>fine</p>").to_string(), "<p>fine</p>");
+	assert_eq!(errors.get(), 0);
+	parser.parse("</b>");
+	parser.parse("</i>");
+	assert_eq!(errors.get(), 2);
+}
+
+#[test]
+fn selectors_are_send_and_sync() {
+	fn assert_send_sync<T: Send + Sync>(_: &T) {}
+	assert_send_sync(&Selectors::compile("article:has(> img), p").unwrap());
+	assert_send_sync(&SelectorCache::new());
+}
+
+#[test]
+fn selector_cache() {
+	let mut cache = SelectorCache::new();
+	assert!(cache.is_empty());
+	let documents = ["<p class=a>1</p><p>2</p>", "<p class=a>3</p>"]
+		.iter()
+		.map(|html| parse_html().one(*html))
+		.collect::<Vec<_>>();
+	let mut texts = Vec::new();
+	for document in &documents {
+		let selectors = cache.get_or_compile("p.a").unwrap();
+		texts.extend(
+			document
+				.select_compiled(selectors)
+				.map(|p| p.text_contents()),
+		);
+	}
+	assert_eq!(texts, ["1", "3"]);
+	assert_eq!(cache.len(), 1);
+	assert!(cache.get_or_compile("p >").is_err());
+	assert_eq!(cache.len(), 1);
+	assert_eq!(format!("{:?}", cache), r#"{"p.a"}"#);
+	cache.clear();
+	assert!(cache.is_empty());
+}
+
+#[test]
+fn select_children() {
+	let document = parse_html().one(
+		"<ul><li class=a>1<ul><li class=a>1.1</li></ul></li>text<li>2</li><li class=a>3</li></ul>",
+	);
+	let ul = document.select_first("ul").unwrap().unwrap();
+	let texts = ul
+		.as_node()
+		.select_children(".a")
+		.unwrap()
+		.map(|li| li.as_node().first_child().unwrap().text_contents())
+		.collect::<Vec<_>>();
+	assert_eq!(texts, ["1", "3"]);
+	assert_eq!(ul.as_node().select_children("li").unwrap().count(), 3);
+	assert_eq!(ul.as_node().select_children("ul").unwrap().count(), 0);
+	assert_eq!(
+		ul.as_node()
+			.select_children("ul > li:last-child")
+			.unwrap()
+			.count(),
+		1
+	);
+
+	let first = ul
+		.as_node()
+		.select_first_child("li:not(.a)")
+		.unwrap()
+		.unwrap();
+	assert_eq!(first.text_contents(), "2");
+	assert!(ul.as_node().select_first_child("p").unwrap().is_none());
+	assert!(ul.as_node().select_first_child("p >").is_err());
+}
+
+#[test]
+fn processing_instruction_accessors() {
+	let document = NodeRef::new_document();
+	let pi = document.create_processing_instruction("xml-stylesheet", r#"href="style.css""#);
+	document.append(pi.clone());
+	document.append(document.create_element("root"));
+	assert_eq!(
+		pi.processing_instruction_target().unwrap(),
+		"xml-stylesheet"
+	);
+	assert_eq!(
+		pi.processing_instruction_data().unwrap(),
+		r#"href="style.css""#
+	);
+	assert!(document.processing_instruction_target().is_none());
+
+	let mut xml = Vec::new();
+	document.serialize_xml(&mut xml).unwrap();
+	let xml = String::from_utf8(xml).unwrap();
+	assert_eq!(
+		xml,
+		r#"<?xml-stylesheet href="style.css"?><root xmlns="http://www.w3.org/1999/xhtml"/>"#
+	);
+
+	let reparsed = parse_xml().one(xml);
+	let pi = reparsed.first_child().unwrap();
+	assert_eq!(
+		pi.processing_instruction_target().unwrap(),
+		"xml-stylesheet"
+	);
+	assert_eq!(
+		pi.processing_instruction_data().unwrap(),
+		r#"href="style.css""#
+	);
+}
+
+#[test]
+fn comment_text() {
+	let html = "<p><!-- BEGIN -->name<!-- END --></p><!--[if IE]>old<![endif]-->";
+	let document = parse_html().one(html);
+	let texts = document
+		.comments()
+		.map(|comment| comment.as_node().comment_text().unwrap())
+		.collect::<Vec<_>>();
+	assert_eq!(texts, [" BEGIN ", " END ", "[if IE]>old<![endif]"]);
+
+	let p = document.select_first("p").unwrap().unwrap();
+	assert!(p.as_node().comment_text().is_none());
+	assert!(!p.as_node().set_comment_text("ignored"));
+
+	for comment in document.comments() {
+		let text = comment.borrow().trim().to_lowercase();
+		assert!(comment.as_node().set_comment_text(&text));
+	}
+	assert_eq!(p.as_node().to_string(), "<p><!--begin-->name<!--end--></p>");
+}
+
+#[test]
+fn raw_text_serialization() {
+	let html = "<html><head><style>a > b { content: \"&\" }</style>\
+		<script>if (a < b && c > d) { x = '</p>'; }</script></head>\
+		<body><textarea>a &lt; b &amp; c</textarea><p>a &lt; b</p></body></html>";
+	let document = parse_html().one(html);
+	assert_eq!(document.to_string(), html);
+	assert!(document.deep_eq(&parse_html().one(document.to_string())));
+
+	let script = document.select_first("script").unwrap().unwrap();
+	let source = "if (a < b && c > d) { x = '</p>'; }";
+	assert_eq!(script.text_contents(), source);
+	assert_eq!(script.as_node().inner_html().unwrap(), source);
+	let mut bytes = Vec::new();
+	script
+		.as_node()
+		.serialize_inner_with_opts(&mut bytes, &HtmlSerializeOpts::default())
+		.unwrap();
+	assert_eq!(String::from_utf8(bytes).unwrap(), source);
+
+	// Text in escapable raw text elements such as `<textarea>` is escaped, as the spec requires.
+	let textarea = document.select_first("textarea").unwrap().unwrap();
+	assert_eq!(textarea.text_contents(), "a < b & c");
+	assert_eq!(textarea.as_node().inner_html().unwrap(), "a &lt; b &amp; c");
+}
+
+#[test]
+fn serialize_xml_escaping_and_cdata() {
+	let xml = "<svg xmlns=\"http://www.w3.org/2000/svg\">\
+		<script><![CDATA[if (a < b && c) { s = \"]]]]><![CDATA[>\"; }]]></script>\
+		<text title=\"a&#9;b&#10;&quot;c&quot;\">x &gt; y &amp; z&#13;</text></svg>";
+	let document = parse_xml().one(xml);
+	let script = document.select_first("script").unwrap().unwrap();
+	assert_eq!(script.text_contents(), "if (a < b && c) { s = \"]]>\"; }");
+	let text = document.select_first("text").unwrap().unwrap();
+	assert_eq!(text.attributes.borrow().get("title"), Some("a\tb\n\"c\""));
+	assert_eq!(text.text_contents(), "x > y & z\r");
+
+	// By default, text is escaped and whitespace in attributes is kept with character references.
+	let mut bytes = Vec::new();
+	document.serialize_xml(&mut bytes).unwrap();
+	let escaped = String::from_utf8(bytes).unwrap();
+	assert_eq!(
+		escaped,
+		"<svg xmlns=\"http://www.w3.org/2000/svg\">\
+		<script>if (a &lt; b &amp;&amp; c) { s = \"]]&gt;\"; }</script>\
+		<text title=\"a&#9;b&#10;&quot;c&quot;\">x &gt; y &amp; z&#13;</text></svg>"
+	);
+	assert!(document.deep_eq(&parse_xml().one(escaped)));
+
+	let mut bytes = Vec::new();
+	document
+		.serialize_xml_with_opts(
+			&mut bytes,
+			&XmlSerializeOpts {
+				cdata_elements: vec![local_name!("script")],
+				..Default::default()
+			},
+		)
+		.unwrap();
+	let output = String::from_utf8(bytes).unwrap();
+	assert_eq!(output, xml);
+	assert!(document.deep_eq(&parse_xml().one(output)));
+}
+
+#[test]
+fn detach_children() {
+	let document = parse_html().one("<div><p>1<b>2</b></p>text<!-- c --><p>3</p></div><i></i>");
+	let div = document.select_first("div").unwrap().unwrap();
+	let children = div.as_node().children().collect::<Vec<_>>();
+	assert_eq!(children.len(), 4);
+
+	div.as_node().detach_children();
+	assert!(div.as_node().first_child().is_none());
+	assert!(div.as_node().last_child().is_none());
+	assert_eq!(div.as_node().to_string(), "<div></div>");
+	assert_eq!(
+		document
+			.select_first("body")
+			.unwrap()
+			.unwrap()
+			.as_node()
+			.to_string(),
+		"<body><div></div><i></i></body>"
+	);
+	for child in &children {
+		assert!(child.parent().is_none());
+		assert!(child.previous_sibling().is_none());
+		assert!(child.next_sibling().is_none());
+	}
+	assert_eq!(children[0].to_string(), "<p>1<b>2</b></p>");
+	assert_eq!(children[1].text_contents(), "text");
+
+	// Detached children can be reinserted elsewhere.
+	div.as_node().append(children[3].clone());
+	assert_eq!(div.as_node().to_string(), "<div><p>3</p></div>");
+	div.as_node().detach_children();
+	div.as_node().detach_children();
+	assert!(div.as_node().first_child().is_none());
+}
+
+#[test]
+fn replace_children() {
+	let document =
+		parse_html().one("<div id=a><p>1</p><p>2</p></div><div id=b><i>3</i><i>4</i></div>");
+	let a = document.select_first("#a").unwrap().unwrap();
+	let b = document.select_first("#b").unwrap().unwrap();
+	let old = a.as_node().children().collect::<Vec<_>>();
+
+	let mut new_children = b.as_node().children().collect::<Vec<_>>();
+	new_children.push(NodeRef::new_text("5"));
+	new_children.push(old[1].clone());
+	a.as_node().replace_children(new_children);
+	assert_eq!(
+		a.as_node().to_string(),
+		r#"<div id="a"><i>3</i><i>4</i>5<p>2</p></div>"#
+	);
+	assert_eq!(b.as_node().to_string(), r#"<div id="b"></div>"#);
+	assert!(old[0].parent().is_none());
+
+	a.as_node().replace_children(None);
+	assert!(a.as_node().first_child().is_none());
+}
+
+#[test]
+#[should_panic(expected = "cannot insert a node into itself")]
+fn replace_children_with_ancestor() {
+	let document = parse_html().one("<div><p><b>x</b></p></div>");
+	let div = document.select_first("div").unwrap().unwrap();
+	let b = document.select_first("b").unwrap().unwrap();
+	b.as_node().replace_children(vec![div.as_node().clone()]);
+}
+
+#[test]
+fn insertion_cycles_are_rejected() {
+	use std::panic::{catch_unwind, AssertUnwindSafe};
+
+	let document = parse_html().one("<div><p><b>x</b></p></div>");
+	let div = document
+		.select_first("div")
+		.unwrap()
+		.unwrap()
+		.as_node()
+		.clone();
+	let p = div.first_child().unwrap();
+	let b = p.first_child().unwrap();
+	let before = document.to_string();
+
+	let attempts: Vec<Box<dyn Fn()>> = vec![
+		Box::new(|| b.append(div.clone())),
+		Box::new(|| b.prepend(p.clone())),
+		Box::new(|| b.insert_before(div.clone())),
+		Box::new(|| b.insert_after(p.clone())),
+		Box::new(|| p.append(p.clone())),
+		Box::new(|| b.replace_with(div.clone())),
+		Box::new(|| b.wrap(p.clone())),
+		Box::new(|| p.wrap(p.clone())),
+	];
+	for attempt in attempts {
+		let error = catch_unwind(AssertUnwindSafe(attempt)).unwrap_err();
+		assert_eq!(
+			error.downcast_ref::<&str>(),
+			Some(&"cannot insert a node into itself or one of its descendants")
+		);
+	}
+	assert_eq!(document.to_string(), before);
+
+	// Template contents count as descendants of their template.
+	let document = parse_html().one("<template><p>x<template>y</template></p></template>");
+	let outer = document.select_first("template").unwrap().unwrap();
+	let contents = outer.template_contents.clone().unwrap();
+	let inner_contents = contents
+		.select_first("template")
+		.unwrap()
+		.unwrap()
+		.template_contents
+		.clone()
+		.unwrap();
+	let outer = outer.as_node().clone();
+	let before = document.to_string();
+	let attempts: Vec<Box<dyn Fn()>> = vec![
+		Box::new(|| contents.append(outer.clone())),
+		Box::new(|| inner_contents.prepend(outer.clone())),
+		Box::new(|| contents.first_child().unwrap().append(outer.clone())),
+	];
+	for attempt in attempts {
+		assert!(catch_unwind(AssertUnwindSafe(attempt)).is_err());
+	}
+	assert_eq!(document.to_string(), before);
+	contents.append(NodeRef::new_text("z"));
+	assert_eq!(
+		outer.to_string(),
+		"<template><p>x<template>y</template></p>z</template>"
+	);
+
+	// A descendant can still be moved out, next to its ancestor.
+	b.append(NodeRef::new_text("y"));
+	div.insert_before(b.clone());
+	assert_eq!(
+		div.parent().unwrap().to_string(),
+		"<body><b>xy</b><div><p></p></div></body>"
+	);
+}
+
+#[test]
+fn dropped_trees_are_freed() {
+	use std::rc::{Rc, Weak};
+
+	let document =
+		parse_html().one("<div><p>text<b>x</b></p><!-- c --></div><template><i>t</i></template>");
+	let weak = |node: NodeRef| Rc::downgrade(&node.0);
+	let nodes = document
+		.inclusive_descendants()
+		.map(weak)
+		.collect::<Vec<_>>();
+	let template = document.select_first("template").unwrap().unwrap();
+	let contents = template.template_contents.clone().unwrap();
+	let template_nodes = contents
+		.inclusive_descendants()
+		.map(weak)
+		.collect::<Vec<_>>();
+	drop((template, contents));
+	let alive = |nodes: &[Weak<Node>]| nodes.iter().filter(|node| node.upgrade().is_some()).count();
+
+	// A detached node is kept alive by its own reference,
+	// and keeps neither its former parent nor its former siblings alive.
+	let p = document
+		.select_first("p")
+		.unwrap()
+		.unwrap()
+		.as_node()
+		.clone();
+	p.detach();
+	drop(document);
+	assert_eq!(alive(&nodes), 4);
+	assert_eq!(alive(&template_nodes), 0);
+	assert_eq!(p.inclusive_descendants().count(), 4);
+
+	drop(p);
+	assert_eq!(alive(&nodes), 0);
+}
+
+#[test]
+fn iterate_node_reference() {
+	let document = parse_html().one("<ul><li>1</li>text<li>2<b>3</b></li></ul>");
+	let ul = document
+		.select_first("ul")
+		.unwrap()
+		.unwrap()
+		.as_node()
+		.clone();
+	let mut texts = Vec::new();
+	for child in &ul {
+		texts.push(child.text_contents());
+	}
+	assert_eq!(texts, ["1", "text", "23"]);
+	assert_eq!((&ul).into_iter().rev().count(), 3);
+	assert!((&ul).into_iter().eq(ul.children()));
+	assert_eq!((&NodeRef::new_text("leaf")).into_iter().count(), 0);
+}
+
+#[test]
+fn select_in_document_order() {
+	let html = r#"
+		<div id=1><p id=2><b id=3></b></p><i id=4></i></div>
+		<section id=5><b id=6><i id=7></i></b></section><p id=8></p>
+	"#;
+	let document = parse_html().one(html);
+	let ids = |selectors: &str| {
+		document
+			.select(selectors)
+			.unwrap()
+			.map(|element| element.get_attribute("id").unwrap())
+			.collect::<Vec<_>>()
+	};
+	// The list order and matching several selectors of the list do not affect the result order.
+	assert_eq!(ids("i, b, p"), ["2", "3", "4", "6", "7", "8"]);
+	assert_eq!(
+		ids("section *, div > *, [id='3']"),
+		["2", "3", "4", "6", "7"]
+	);
+	assert_eq!(ids("body *"), ["1", "2", "3", "4", "5", "6", "7", "8"]);
+
+	let reversed = document
+		.select("b, i")
+		.unwrap()
+		.rev()
+		.map(|element| element.get_attribute("id").unwrap())
+		.collect::<Vec<_>>();
+	assert_eq!(reversed, ["7", "6", "4", "3"]);
+}
+
+#[test]
+fn select_n() {
+	let document = parse_html().one("<p id=1></p><div><p id=2><p id=3></div><p id=4>");
+	let ids = |n| {
+		document
+			.select_n("p", n)
+			.unwrap()
+			.iter()
+			.map(|element| element.get_attribute("id").unwrap())
+			.collect::<Vec<_>>()
+	};
+	assert_eq!(ids(2), ["1", "2"]);
+	assert_eq!(ids(10), ["1", "2", "3", "4"]);
+	assert!(ids(0).is_empty());
+	assert!(document.select_n("p >", 1).is_err());
+}
+
+#[test]
+fn child_and_element_index() {
+	let document =
+		parse_html().one("<ul>text<li id=a></li><!-- c --><li id=b></li>more<li id=c></li></ul>");
+	let ul = document
+		.select_first("ul")
+		.unwrap()
+		.unwrap()
+		.as_node()
+		.clone();
+	let children = ul.children().collect::<Vec<_>>();
+	for (i, child) in children.iter().enumerate() {
+		assert_eq!(child.child_index(), Some(i));
+	}
+	let element_indices = children
+		.iter()
+		.map(|child| child.element_index())
+		.collect::<Vec<_>>();
+	assert_eq!(
+		element_indices,
+		[None, Some(0), None, Some(1), None, Some(2)]
+	);
+	for li in ul.select("li").unwrap() {
+		let n = li.as_node().element_index().unwrap() + 1;
+		let nth = format!("li:nth-child({})", n);
+		assert_eq!(ul.select_first(&nth).unwrap().unwrap(), li);
+	}
+
+	assert_eq!(document.child_index(), None);
+	let detached = NodeRef::new_text("x");
+	assert_eq!(detached.child_index(), None);
+	assert_eq!(ul.element_index(), Some(0));
+	assert_eq!(ul.parent().unwrap().element_index(), Some(1));
+}
+
+#[test]
+fn css_path() {
+	let html = r#"
+		<div id=main><div></div><div><p>a</p><p id=dup>b</p></div></div>
+		<section><p id=dup>c</p><svg><linearGradient id="1st gradient"/><g/><g><rect/></g></svg></section>
+		<template><p>t</p></template>
+	"#;
+	let document = parse_html().one(html);
+	let path = |selectors: &str| {
+		let element = document.select_first(selectors).unwrap().unwrap();
+		element.as_node().css_path().unwrap()
+	};
+	assert_eq!(
+		path("#main p"),
+		"#main > div:nth-of-type(2) > p:nth-of-type(1)"
+	);
+	assert_eq!(
+		path("#main p + p"),
+		"#main > div:nth-of-type(2) > p:nth-of-type(2)"
+	);
+	assert_eq!(path("section p"), ":root > body > section > p");
+	assert_eq!(
+		path("rect"),
+		":root > body > section > svg > g:nth-of-type(2) > rect"
+	);
+	assert_eq!(path("linearGradient"), r"#\31 st\ gradient");
+	assert_eq!(path("html"), ":root");
+
+	// Every path selects its element and nothing else.
+	for element in document.descendants().elements() {
+		let path = element.as_node().css_path().unwrap();
+		let matches = document.select(&path).unwrap().collect::<Vec<_>>();
+		assert_eq!(matches, [element], "{}", path);
+	}
+
+	let template = document.select_first("template").unwrap().unwrap();
+	let contents = template.template_contents.as_ref().unwrap();
+	let p = contents.first_child().unwrap();
+	assert_eq!(p.css_path().unwrap(), "p");
+	assert_eq!(p.first_child().unwrap().css_path(), None);
+	assert_eq!(document.css_path(), None);
+}
+
+#[test]
+fn xpath() {
+	let html = "<!DOCTYPE html><div><p>a</p></div><div><p>b</p>text<p>c<!-- c --></p></div>";
+	let document = parse_html().one(html);
+	let node = |selectors: &str| {
+		document
+			.select_first(selectors)
+			.unwrap()
+			.unwrap()
+			.as_node()
+			.clone()
+	};
+	assert_eq!(document.xpath(), "/");
+	assert_eq!(node("html").xpath(), "/html");
+	assert_eq!(node("div").xpath(), "/html/body/div[1]");
+	assert_eq!(node("div + div p + p").xpath(), "/html/body/div[2]/p[2]");
+	assert_eq!(
+		node("div + div p").last_child().unwrap().xpath(),
+		"/html/body/div[2]/p[1]/text()"
+	);
+	assert_eq!(document.first_child().unwrap().xpath(), "/node()[1]");
+
+	// Every node is found again from its path, from any node of the tree.
+	let p = node("p");
+	for descendant in document.inclusive_descendants() {
+		let path = descendant.xpath();
+		assert_eq!(p.evaluate_xpath(&path).unwrap(), [descendant], "{}", path);
+	}
+
+	let texts = |nodes: Vec<NodeRef>| {
+		nodes
+			.iter()
+			.map(|node| node.text_contents())
+			.collect::<Vec<_>>()
+	};
+	assert_eq!(
+		texts(document.evaluate_xpath("/html/body/div/p").unwrap()),
+		["a", "b", "c"]
+	);
+	assert_eq!(
+		texts(document.evaluate_xpath("/HTML/BODY/*[2]/p[2]").unwrap()),
+		["c"]
+	);
+	assert_eq!(
+		texts(node("body").evaluate_xpath("div[2]/text()").unwrap()),
+		["text"]
+	);
+	let comments = node("body").evaluate_xpath("div/p[2]/comment()").unwrap();
+	assert_eq!(comments.len(), 1);
+	assert_eq!(comments[0].comment_text().unwrap(), " c ");
+	assert!(document
+		.evaluate_xpath("/html/body/div[3]")
+		.unwrap()
+		.is_empty());
+
+	for path in [
+		"//p",
+		"/html/body/div[@id]",
+		"/html/./body",
+		"p[0]",
+		"p[last()]",
+	] {
+		assert!(document.evaluate_xpath(path).is_err(), "{}", path);
+	}
+	let error = document
+		.evaluate_xpath("/html/body[position() = 1]")
+		.unwrap_err();
+	assert_eq!(
+		error.to_string(),
+		"unsupported XPath step `body[position() = 1]`"
+	);
+
+	// Outside a document, paths start at the topmost node.
+	let detached = NodeRef::element("ul")
+		.child(NodeRef::element("li"))
+		.child(NodeRef::element("li"))
+		.build();
+	let li = detached.last_child().unwrap();
+	assert_eq!(li.xpath(), "/ul/li[2]");
+	assert_eq!(li.evaluate_xpath("/ul/li[2]").unwrap(), [li]);
+	assert_eq!(detached.evaluate_xpath("li").unwrap().len(), 2);
+}
+
+#[test]
+fn select_first_of() {
+	let document = parse_html().one(
+		r#"<div class="price">10</div><main><span class="price-new">8</span><span class="price-new">9</span></main>"#,
+	);
+	let (index, element) = document
+		.select_first_of(&["#price", "main .price-new", ".price"])
+		.unwrap()
+		.unwrap();
+	assert_eq!(index, 1);
+	assert_eq!(element.text_contents(), "8");
+
+	let (index, element) = document
+		.select_first_of(&[".price", "main .price-new"])
+		.unwrap()
+		.unwrap();
+	assert_eq!((index, element.text_contents()), (0, "10".to_owned()));
+
+	assert!(document.select_first_of(&["#a", "#b"]).unwrap().is_none());
+	assert!(document.select_first_of(&[]).unwrap().is_none());
+	assert!(document.select_first_of(&[".price", "main >"]).is_err());
+}
+
+#[test]
+fn attributes_by_namespace_and_prefix() {
+	let xml = r##"<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink" xmlns:foo="urn:foo">
+		<use id="u" xlink:href="#a" foo:x="1" xlink:title="t" foo:y="2"/></svg>"##;
+	let document = parse_xml().one(xml);
+	let names = |iter: &mut dyn Iterator<Item = (&ExpandedName, &crate::Attribute)>| {
+		iter.map(|(name, _)| name.local.to_string())
+			.collect::<Vec<_>>()
+	};
+
+	// The XML parser resolves namespace declarations without keeping them, but the HTML parser keeps them.
+	let html = parse_html().one(
+		r#"<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink" class=c>"#,
+	);
+	let svg = html.select_first("svg").unwrap().unwrap();
+	let attributes = svg.attributes.borrow();
+	assert_eq!(
+		names(&mut attributes.iter_ns(&ns!(xmlns))),
+		["xmlns", "xlink"]
+	);
+	assert_eq!(
+		names(&mut attributes.iter_prefix(&namespace_prefix!("xmlns"))),
+		["xlink"]
+	);
+
+	let element = document.select_first("use").unwrap().unwrap();
+	let attributes = element.attributes.borrow();
+	assert_eq!(
+		names(&mut attributes.iter_ns(&ns!(xlink))),
+		["href", "title"]
+	);
+	assert_eq!(names(&mut attributes.iter_ns(&ns!())), ["id"]);
+	let foo = html5ever::Namespace::from("urn:foo");
+	let values = attributes
+		.iter_ns(&foo)
+		.map(|(_, attr)| attr.value.as_str())
+		.collect::<Vec<_>>();
+	assert_eq!(values, ["1", "2"]);
+	assert_eq!(
+		names(&mut attributes.iter_prefix(&html5ever::Prefix::from("foo"))),
+		["x", "y"]
+	);
+	assert_eq!(attributes.iter_prefix(&namespace_prefix!("svg")).count(), 0);
+}
+
+#[test]
+fn serialize_matching() {
+	let html = r#"<!DOCTYPE html><html><head><title>T</title></head><body class=b>
+		<nav>menu</nav><main id=m><aside>ad</aside><article><h1>A</h1><p>a<b>b</b></p></article>
+		<article class=x><script>if (a < b) {}</script></article></main><footer>f</footer></body></html>"#;
+	let document = parse_html().one(html);
+	let serialize = |node: &NodeRef, selectors: &str| {
+		let mut bytes = Vec::new();
+		node.serialize_matching(&Selectors::compile(selectors).unwrap(), &mut bytes)
+			.unwrap();
+		String::from_utf8(bytes).unwrap()
+	};
+	assert_eq!(
+		serialize(&document, "article"),
+		"<!DOCTYPE html><html><body class=\"b\"><main id=\"m\">\
+		<article><h1>A</h1><p>a<b>b</b></p></article>\
+		<article class=\"x\"><script>if (a < b) {}</script></article></main></body></html>"
+	);
+	// Matches inside other matches are written once, with their ancestor.
+	assert_eq!(
+		serialize(&document, "#m > article:not(.x), b, title"),
+		"<!DOCTYPE html><html><head><title>T</title></head><body class=\"b\"><main id=\"m\">\
+		<article><h1>A</h1><p>a<b>b</b></p></article></main></body></html>"
+	);
+
+	let main = document.select_first("main").unwrap().unwrap();
+	assert_eq!(
+		serialize(main.as_node(), "h1"),
+		"<main id=\"m\"><article><h1>A</h1></article></main>"
+	);
+	assert_eq!(
+		serialize(main.as_node(), "main"),
+		main.as_node().to_string()
+	);
+	assert_eq!(serialize(&document, "table"), "");
+}
+
+#[test]
+fn element_and_attribute_name_counts() {
+	let html = r##"<div class=a id=x><p class=b>1</p><p style="">2<a href="/" class=c>3</a></p>
+		<template><p onclick="x()">t</p></template><svg><use xlink:href="#x"/></svg></div>"##;
+	let document = parse_html().one(html);
+	let div = document.select_first("div").unwrap().unwrap();
+
+	let elements = div.as_node().element_name_counts();
+	assert_eq!(elements[&local_name!("p")], 3);
+	assert_eq!(elements[&local_name!("div")], 1);
+	assert_eq!(elements[&local_name!("use")], 1);
+	assert_eq!(elements.values().sum::<usize>(), 8);
+	assert!(!elements.contains_key(&local_name!("body")));
+
+	let attributes = div.as_node().attribute_name_counts();
+	assert_eq!(attributes[&ExpandedName::new(ns!(), "class")], 3);
+	assert_eq!(attributes[&ExpandedName::new(ns!(), "style")], 1);
+	assert_eq!(attributes[&ExpandedName::new(ns!(), "onclick")], 1);
+	assert_eq!(attributes[&ExpandedName::new(ns!(xlink), "href")], 1);
+	assert_eq!(attributes[&ExpandedName::new(ns!(), "href")], 1);
+	assert_eq!(attributes.len(), 6);
+
+	assert!(NodeRef::new_text("x").element_name_counts().is_empty());
+}
@@ -0,0 +1,252 @@
+use html5ever::LocalName;
+use std::cell::RefCell;
+
+use crate::iter::{NodeEdge, NodeIterator};
+use crate::tree::{NodeData, NodeRef};
+
+impl NodeRef {
+	/// Return an approximation of the text of this subtree as it would be rendered,
+	/// like the DOM `innerText` getter but without any CSS.
+	///
+	/// Block-level elements such as `<p>`, `<div>` and `<li>` start on a new line,
+	/// `<br>` is a line break, and runs of whitespace collapse to a single space
+	/// except inside `<pre>`. The contents of `<head>`, `<script>`, `<style>`,
+	/// `<noscript>` and `<template>` are skipped.
+	pub fn inner_text(&self) -> String {
+		let mut text = InnerText::default();
+		let mut preformatted_depth = 0;
+		let mut stack = vec![NodeEdge::Start(self.clone())];
+		while let Some(edge) = stack.pop() {
+			match edge {
+				NodeEdge::Start(node) => {
+					match node.data() {
+						NodeData::Element(_) => {
+							let name = html_local_name(&node);
+							if name.is_some_and(is_hidden) {
+								continue;
+							}
+							if name == Some(&local_name!("br")) {
+								text.line_break();
+							} else if name.is_some_and(is_block) {
+								text.pending_break = true;
+							}
+							if name == Some(&local_name!("pre")) {
+								preformatted_depth += 1;
+							}
+						}
+						NodeData::Text(contents) => {
+							text.push(&contents.borrow(), preformatted_depth > 0);
+							continue;
+						}
+						NodeData::Document(_) | NodeData::DocumentFragment => {}
+						_ => continue,
+					}
+					stack.push(NodeEdge::End(node.clone()));
+					stack.extend(node.children().rev().map(NodeEdge::Start));
+				}
+				NodeEdge::End(node) => {
+					let name = html_local_name(&node);
+					if name.is_some_and(is_block) {
+						text.pending_break = true;
+					}
+					if name == Some(&local_name!("pre")) {
+						preformatted_depth -= 1;
+					}
+				}
+			}
+		}
+		text.output
+	}
+
+	/// Return the concatenation of the text nodes in this subtree,
+	/// skipping the contents of `<script>`, `<style>`, `<noscript>` and `<template>` elements.
+	///
+	/// Unlike `inner_text`, whitespace is kept as is.
+	pub fn text_contents_visible(&self) -> String {
+		let mut s = String::new();
+		self.for_each_visible_text(|text| s.push_str(&text.borrow()));
+		s
+	}
+
+	/// Return the concatenation of all text nodes in this subtree, like `text_contents`,
+	/// with each run of whitespace collapsed to a single space and none at either end.
+	///
+	/// Whitespace here is ASCII whitespace, as in HTML, so non-breaking spaces are kept.
+	pub fn trimmed_text(&self) -> String {
+		let mut text = InnerText::default();
+		for text_node in self.inclusive_descendants().text_nodes() {
+			text.push(&text_node.borrow(), false);
+		}
+		text.output
+	}
+
+	/// Replace every occurrence of `pattern` with `replacement` in the text of this subtree,
+	/// skipping the same elements as `text_contents_visible`.
+	///
+	/// Each text node is searched separately, so an occurrence split across nodes,
+	/// such as `fo<b>o</b>`, is not replaced.
+	/// Call `normalize` first to merge text nodes that are adjacent siblings.
+	pub fn replace_text(&self, pattern: &str, replacement: &str) {
+		if pattern.is_empty() {
+			return;
+		}
+		self.for_each_visible_text(|text| {
+			if text.borrow().contains(pattern) {
+				let replaced = text.borrow().replace(pattern, replacement);
+				*text.borrow_mut() = replaced;
+			}
+		});
+	}
+
+	/// Replace every match of `regex` in the text of this subtree, like `replace_text`.
+	///
+	/// `replacement` can refer to capture groups, as in `Regex::replace_all`.
+	#[cfg(feature = "regex")]
+	pub fn replace_text_regex(&self, regex: &regex::Regex, replacement: &str) {
+		self.for_each_visible_text(|text| {
+			let replaced = match regex.replace_all(&text.borrow(), replacement) {
+				std::borrow::Cow::Owned(replaced) => replaced,
+				std::borrow::Cow::Borrowed(_) => return,
+			};
+			*text.borrow_mut() = replaced;
+		});
+	}
+
+	/// Call `f` on each text node of this subtree, in tree order,
+	/// except those inside `<script>`, `<style>`, `<noscript>` and `<template>` elements.
+	fn for_each_visible_text<F: FnMut(&RefCell<String>)>(&self, mut f: F) {
+		let mut hidden_depth = 0;
+		for edge in self.traverse_inclusive() {
+			match edge {
+				NodeEdge::Start(node) => {
+					if let Some(text) = node.as_text() {
+						if hidden_depth == 0 {
+							f(text);
+						}
+					} else if html_local_name(&node).is_some_and(is_script_like) {
+						hidden_depth += 1;
+					}
+				}
+				NodeEdge::End(node) => {
+					if html_local_name(&node).is_some_and(is_script_like) {
+						hidden_depth -= 1;
+					}
+				}
+			}
+		}
+	}
+}
+
+/// Accumulates rendered text, deferring separators until the next visible text.
+#[derive(Default)]
+struct InnerText {
+	output: String,
+	pending_break: bool,
+	pending_space: bool,
+}
+
+impl InnerText {
+	fn push(&mut self, text: &str, preformatted: bool) {
+		if preformatted {
+			if !text.is_empty() {
+				self.flush_separator();
+				self.output.push_str(text);
+			}
+			return;
+		}
+		if text.starts_with(|c: char| c.is_ascii_whitespace()) {
+			self.pending_space = true;
+		}
+		let mut words = text.split_ascii_whitespace().peekable();
+		while let Some(word) = words.next() {
+			self.flush_separator();
+			self.output.push_str(word);
+			self.pending_space = words.peek().is_some();
+		}
+		if text.ends_with(|c: char| c.is_ascii_whitespace()) {
+			self.pending_space = true;
+		}
+	}
+
+	fn line_break(&mut self) {
+		self.output.push('\n');
+		self.pending_break = false;
+		self.pending_space = false;
+	}
+
+	fn flush_separator(&mut self) {
+		if !self.output.is_empty() && !self.output.ends_with('\n') {
+			if self.pending_break {
+				self.output.push('\n');
+			} else if self.pending_space {
+				self.output.push(' ');
+			}
+		}
+		self.pending_break = false;
+		self.pending_space = false;
+	}
+}
+
+/// Return the local name of an HTML element.
+fn html_local_name(node: &NodeRef) -> Option<&LocalName> {
+	node.as_element()
+		.filter(|element| element.name.ns == ns!(html))
+		.map(|element| &element.name.local)
+}
+
+/// Elements whose contents are not page text.
+fn is_script_like(name: &LocalName) -> bool {
+	matches!(
+		*name,
+		local_name!("script")
+			| local_name!("style")
+			| local_name!("noscript")
+			| local_name!("template")
+	)
+}
+
+fn is_hidden(name: &LocalName) -> bool {
+	*name == local_name!("head") || is_script_like(name)
+}
+
+fn is_block(name: &LocalName) -> bool {
+	matches!(
+		*name,
+		local_name!("address")
+			| local_name!("article")
+			| local_name!("aside")
+			| local_name!("blockquote")
+			| local_name!("caption")
+			| local_name!("dd")
+			| local_name!("details")
+			| local_name!("dialog")
+			| local_name!("div")
+			| local_name!("dl")
+			| local_name!("dt")
+			| local_name!("fieldset")
+			| local_name!("figcaption")
+			| local_name!("figure")
+			| local_name!("footer")
+			| local_name!("form")
+			| local_name!("h1")
+			| local_name!("h2")
+			| local_name!("h3")
+			| local_name!("h4")
+			| local_name!("h5")
+			| local_name!("h6")
+			| local_name!("header")
+			| local_name!("hgroup")
+			| local_name!("hr")
+			| local_name!("li")
+			| local_name!("main")
+			| local_name!("nav")
+			| local_name!("ol")
+			| local_name!("p")
+			| local_name!("pre")
+			| local_name!("section")
+			| local_name!("summary")
+			| local_name!("table")
+			| local_name!("tr")
+			| local_name!("ul")
+	)
+}
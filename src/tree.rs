@@ -1,13 +1,15 @@
+use encoding_rs::Encoding;
 use html5ever::tree_builder::QuirksMode;
-use html5ever::QualName;
+use html5ever::{LocalName, QualName};
 use std::cell::{Cell, RefCell};
 use std::fmt;
+use std::hash::{Hash, Hasher};
 use std::ops::Deref;
 use std::rc::{Rc, Weak};
 
 use crate::attributes::{Attribute, Attributes, ExpandedName};
 use crate::cell_extras::*;
-use crate::iter::NodeIterator;
+use crate::iter::{NodeEdge, NodeIterator};
 
 /// Node data specific to the node type.
 #[derive(Debug, PartialEq, Clone)]
@@ -61,11 +63,61 @@ pub struct ElementData {
 	pub template_contents: Option<NodeRef>,
 }
 
+impl ElementData {
+	/// Return whether the element has the given attribute in the null namespace.
+	#[inline]
+	pub fn has_attribute<A: Into<LocalName>>(&self, local_name: A) -> bool {
+		self.attributes.borrow().contains(local_name)
+	}
+
+	/// Return a copy of the value of the given attribute in the null namespace.
+	#[inline]
+	pub fn get_attribute<A: Into<LocalName>>(&self, local_name: A) -> Option<String> {
+		self.attributes.borrow().get(local_name).map(str::to_owned)
+	}
+
+	/// Set the value of the given attribute in the null namespace.
+	#[inline]
+	pub fn set_attribute<A: Into<LocalName>, V: Into<String>>(&self, local_name: A, value: V) {
+		self.attributes
+			.borrow_mut()
+			.insert(local_name, value.into());
+	}
+
+	/// Remove the given attribute in the null namespace. Return its value, if it was present.
+	#[inline]
+	pub fn remove_attribute<A: Into<LocalName>>(&self, local_name: A) -> Option<String> {
+		self.attributes
+			.borrow_mut()
+			.remove(local_name)
+			.map(|attr| attr.value)
+	}
+
+	/// Return a copy of the `id` attribute, if present.
+	#[inline]
+	pub fn id(&self) -> Option<String> {
+		self.get_attribute(local_name!("id"))
+	}
+
+	/// Return copies of the classes in the `class` attribute, in order.
+	///
+	/// The attribute is split on ASCII whitespace, as for selector matching.
+	pub fn classes(&self) -> Vec<String> {
+		self.attributes
+			.borrow()
+			.tokens(local_name!("class"))
+			.map(str::to_owned)
+			.collect()
+	}
+}
+
 /// Data specific to document nodes.
 #[derive(Debug, PartialEq, Clone)]
 pub struct DocumentData {
 	#[doc(hidden)]
 	pub _quirks_mode: Cell<QuirksMode>,
+	#[doc(hidden)]
+	pub _encoding: Cell<Option<&'static Encoding>>,
 }
 
 impl DocumentData {
@@ -74,6 +126,13 @@ impl DocumentData {
 	pub fn quirks_mode(&self) -> QuirksMode {
 		self._quirks_mode.get()
 	}
+
+	/// The character encoding the document was decoded from,
+	/// if it was parsed from bytes with `parse_html_from_bytes`.
+	#[inline]
+	pub fn encoding(&self) -> Option<&'static Encoding> {
+		self._encoding.get()
+	}
 }
 
 /// A strong reference to a node.
@@ -92,6 +151,10 @@ impl DocumentData {
 /// To avoid detroying nodes prematurely,
 /// programs typically hold a strong reference to the root of a document
 /// until they’re done with that document.
+///
+/// Equality and hashing are by identity: two `NodeRef`s are equal
+/// when they refer to the same node, not when their subtrees look the same.
+/// This makes `NodeRef` usable as a `HashMap` or `HashSet` key.
 #[derive(Clone, Debug)]
 pub struct NodeRef(pub Rc<Node>);
 
@@ -107,13 +170,23 @@ impl Eq for NodeRef {}
 impl PartialEq for NodeRef {
 	#[inline]
 	fn eq(&self, other: &NodeRef) -> bool {
-		let a: *const Node = &*self.0;
-		let b: *const Node = &*other.0;
-		a == b
+		Rc::ptr_eq(&self.0, &other.0)
+	}
+}
+
+impl Hash for NodeRef {
+	#[inline]
+	fn hash<H: Hasher>(&self, state: &mut H) {
+		Rc::as_ptr(&self.0).hash(state)
 	}
 }
 
 /// A node inside a DOM-like tree.
+///
+/// Each node owns its first child and its next sibling.
+/// Links to its parent, previous sibling and last child are weak,
+/// so a tree is freed as soon as nothing outside it holds a `NodeRef` to one of its nodes,
+/// and a detached subtree does not keep its former tree alive.
 pub struct Node {
 	parent: Cell<Option<Weak<Node>>>,
 	previous_sibling: Cell<Option<Weak<Node>>>,
@@ -169,6 +242,17 @@ impl Drop for Node {
 					rc = child;
 					continue;
 				}
+				// Template contents are owned through `ElementData` rather than a `Cell`,
+				// so take their children instead, leaving an empty fragment to drop.
+				if let Some(child) = rc
+					.as_element()
+					.and_then(|element| element.template_contents.as_ref())
+					.and_then(|contents| contents.first_child.take_if_unique_strong())
+				{
+					stack.push(rc);
+					rc = child;
+					continue;
+				}
 				if let Some(sibling) = rc.next_sibling.take_if_unique_strong() {
 					// The previous value of `rc: Rc<Node>` is dropped here.
 					// Since it was unique, the corresponding `Node` is dropped as well.
@@ -268,9 +352,233 @@ impl NodeRef {
 	pub fn new_document() -> NodeRef {
 		NodeRef::new(NodeData::Document(DocumentData {
 			_quirks_mode: Cell::new(QuirksMode::NoQuirks),
+			_encoding: Cell::new(None),
 		}))
 	}
 
+	/// Create a new HTML element, like the DOM `document.createElement`.
+	///
+	/// The name is ASCII-lowercased. The new node is not inserted anywhere,
+	/// and nothing ties it to this document: this is a convenience for
+	/// `NodeRef::new_element`, usually called on a document node.
+	pub fn create_element(&self, name: &str) -> NodeRef {
+		NodeRef::new_element(
+			QualName::new(None, ns!(html), LocalName::from(name.to_ascii_lowercase())),
+			None,
+		)
+	}
+
+	/// Create a new text node, like the DOM `document.createTextNode`.
+	///
+	/// As with `create_element`, the new node is not inserted anywhere.
+	#[inline]
+	pub fn create_text_node(&self, text: &str) -> NodeRef {
+		NodeRef::new_text(text)
+	}
+
+	/// Create a new comment node, like the DOM `document.createComment`.
+	///
+	/// As with `create_element`, the new node is not inserted anywhere.
+	#[inline]
+	pub fn create_comment(&self, text: &str) -> NodeRef {
+		NodeRef::new_comment(text)
+	}
+
+	/// Create a new processing instruction node,
+	/// like the DOM `document.createProcessingInstruction`.
+	///
+	/// As with `create_element`, the new node is not inserted anywhere.
+	#[inline]
+	pub fn create_processing_instruction(&self, target: &str, data: &str) -> NodeRef {
+		NodeRef::new_processing_instruction(target, data)
+	}
+
+	/// Return a deep copy of this node and its descendants.
+	///
+	/// The copy is a new tree with no parent or siblings,
+	/// and shares nothing with the original: changes to one are not visible in the other.
+	/// Template contents are copied as well.
+	pub fn clone_tree(&self) -> NodeRef {
+		// Walk the tree instead of recursing, to support deep trees.
+		let mut ancestors: Vec<NodeRef> = Vec::new();
+		let mut root = None;
+		for edge in self.traverse_inclusive() {
+			match edge {
+				NodeEdge::Start(node) => {
+					let copy = node.clone_node();
+					if let Some(parent) = ancestors.last() {
+						parent.append(copy.clone());
+					}
+					ancestors.push(copy);
+				}
+				NodeEdge::End(_) => root = ancestors.pop(),
+			}
+		}
+		root.expect("traversal ends with the root")
+	}
+
+	/// Return a deep copy of this node and its descendants, like `clone_tree`,
+	/// where `f` can replace nodes.
+	///
+	/// `f` is called on each node of the original tree, in tree order.
+	/// If it returns `None`, the node is copied and `f` is called on its children.
+	/// If it returns `Some(replacement)`, the replacement is used as is in place of the node
+	/// and its descendants: `f` is not called on the original’s descendants
+	/// nor on the replacement’s. A replacement that is a node of the original tree
+	/// or otherwise has a parent is copied with `clone_tree` instead of being moved.
+	///
+	/// Template contents are mapped as well, and the original tree is left unchanged.
+	pub fn map_tree<F>(&self, mut f: F) -> NodeRef
+	where
+		F: FnMut(&NodeRef) -> Option<NodeRef>,
+	{
+		// Walk the tree instead of recursing, to support deep trees.
+		// Each original node to visit is paired with the copy of its parent.
+		let mut stack = vec![(self.clone(), None)];
+		let mut root = None;
+		while let Some((node, parent)) = stack.pop() {
+			let copy = match f(&node) {
+				Some(replacement) if replacement.parent().is_some() || replacement == *self => {
+					replacement.clone_tree()
+				}
+				Some(replacement) => replacement,
+				None => {
+					let copy = match node.as_element() {
+						// Unlike `clone_node`, this leaves template contents empty, for mapped copies.
+						Some(element) => NodeRef::new_element(
+							element.name.clone(),
+							element.attributes.borrow().map.clone(),
+						),
+						None => node.clone_node(),
+					};
+					let copy_root = copy.content_root();
+					let children = node.content_root().children().rev();
+					stack.extend(children.map(|child| (child, Some(copy_root.clone()))));
+					copy
+				}
+			};
+			match parent {
+				Some(parent) => parent.append(copy),
+				None => root = Some(copy),
+			}
+		}
+		root.expect("the root is visited first")
+	}
+
+	/// Return whether this subtree and `other` are structurally identical:
+	/// same node types, element names, attributes, text and children, in the same order.
+	///
+	/// Unlike `==`, which compares node identity, this compares contents,
+	/// so a tree is `deep_eq` to its `clone_tree`.
+	/// Text is compared literally, including whitespace;
+	/// call `normalize` on both trees first to ignore how text is split into nodes.
+	pub fn deep_eq(&self, other: &NodeRef) -> bool {
+		let mut edges = self.traverse_inclusive();
+		let mut other_edges = other.traverse_inclusive();
+		loop {
+			match (edges.next(), other_edges.next()) {
+				(Some(NodeEdge::Start(a)), Some(NodeEdge::Start(b))) => {
+					if !a.shallow_eq(&b) {
+						return false;
+					}
+				}
+				(Some(NodeEdge::End(_)), Some(NodeEdge::End(_))) => {}
+				(None, None) => return true,
+				_ => return false,
+			}
+		}
+	}
+
+	/// Compare the data of two nodes, ignoring their children but including template contents.
+	fn shallow_eq(&self, other: &NodeRef) -> bool {
+		match (self.data(), other.data()) {
+			(NodeData::Element(a), NodeData::Element(b)) => {
+				a.name == b.name
+					&& *a.attributes.borrow() == *b.attributes.borrow()
+					&& match (&a.template_contents, &b.template_contents) {
+						(Some(a), Some(b)) => a.deep_eq(b),
+						(None, None) => true,
+						_ => false,
+					}
+			}
+			(NodeData::Text(a), NodeData::Text(b))
+			| (NodeData::Comment(a), NodeData::Comment(b)) => *a.borrow() == *b.borrow(),
+			(NodeData::ProcessingInstruction(a), NodeData::ProcessingInstruction(b)) => {
+				*a.borrow() == *b.borrow()
+			}
+			(NodeData::Doctype(a), NodeData::Doctype(b)) => a == b,
+			(NodeData::Document(_), NodeData::Document(_))
+			| (NodeData::DocumentFragment, NodeData::DocumentFragment) => true,
+			_ => false,
+		}
+	}
+
+	/// Merge adjacent text nodes and remove empty text nodes in this subtree,
+	/// like the DOM `Node.normalize()`.
+	pub fn normalize(&self) {
+		// Changing children while traversing would invalidate the traversal, so collect first.
+		let parents = self
+			.inclusive_descendants()
+			.filter(|node| node.first_child().is_some())
+			.collect::<Vec<_>>();
+		for parent in parents {
+			let mut next_child = parent.first_child();
+			while let Some(child) = next_child {
+				next_child = child.next_sibling();
+				let text = match child.as_text() {
+					Some(text) => text,
+					None => continue,
+				};
+				while let Some(following) = next_child.clone() {
+					match following.as_text() {
+						Some(following_text) => {
+							text.borrow_mut().push_str(&following_text.borrow());
+							next_child = following.next_sibling();
+							following.detach();
+						}
+						None => break,
+					}
+				}
+				if text.borrow().is_empty() {
+					child.detach();
+				}
+			}
+		}
+	}
+
+	/// Return a copy of this node without its children.
+	fn clone_node(&self) -> NodeRef {
+		NodeRef::new(match self.data() {
+			NodeData::Element(element) => NodeData::Element(ElementData {
+				name: element.name.clone(),
+				attributes: RefCell::new(Attributes::new(element.attributes.borrow().map.clone())),
+				template_contents: element.template_contents.as_ref().map(NodeRef::clone_tree),
+			}),
+			data => data.clone(),
+		})
+	}
+
+	/// Return the quirks mode of the document this node belongs to,
+	/// as recorded by the HTML parser.
+	///
+	/// Returns `None` if the root of this node’s tree is not a document node.
+	pub fn quirks_mode(&self) -> Option<QuirksMode> {
+		self.inclusive_ancestors()
+			.last()
+			.and_then(|root| root.as_document().map(DocumentData::quirks_mode))
+	}
+
+	/// Return the character encoding of the document this node belongs to,
+	/// if it was parsed from bytes with `parse_html_from_bytes`.
+	///
+	/// Returns `None` for documents parsed from text,
+	/// and if the root of this node’s tree is not a document node.
+	pub fn document_encoding(&self) -> Option<&'static Encoding> {
+		self.inclusive_ancestors()
+			.last()
+			.and_then(|root| root.as_document().and_then(DocumentData::encoding))
+	}
+
 	/// Return the concatenation of all text nodes in this subtree.
 	pub fn text_contents(&self) -> String {
 		let mut s = String::new();
@@ -297,6 +605,28 @@ impl Node {
 		}
 	}
 
+	/// If this node is an element, return its local name.
+	#[inline]
+	pub fn local_name(&self) -> Option<&LocalName> {
+		self.as_element().map(|element| &element.name.local)
+	}
+
+	/// If this node is an element, return its tag name.
+	///
+	/// This is the ASCII-lowercased local name for HTML elements,
+	/// and the qualified name, with its prefix if any, for other elements.
+	/// Unlike the DOM `tagName`, HTML tag names are not uppercased.
+	pub fn tag_name(&self) -> Option<String> {
+		self.as_element().map(|element| {
+			let name = &element.name;
+			match name.prefix {
+				_ if name.ns == ns!(html) => name.local.as_ref().to_ascii_lowercase(),
+				Some(ref prefix) => format!("{}:{}", prefix, name.local),
+				None => name.local.to_string(),
+			}
+		})
+	}
+
 	/// If this node is a text node, return a reference to its contents.
 	#[inline]
 	pub fn as_text(&self) -> Option<&RefCell<String>> {
@@ -315,7 +645,58 @@ impl Node {
 		}
 	}
 
-	/// If this node is a document, return a reference to doctype-specific data.
+	/// If this node is a comment, return a copy of its text.
+	///
+	/// The text does not include the `<!--` and `-->` delimiters.
+	#[inline]
+	pub fn comment_text(&self) -> Option<String> {
+		self.as_comment().map(|text| text.borrow().clone())
+	}
+
+	/// If this node is a comment, replace its text and return `true`.
+	///
+	/// Does nothing and returns `false` for other kinds of nodes.
+	#[inline]
+	pub fn set_comment_text(&self, text: &str) -> bool {
+		match self.as_comment() {
+			Some(contents) => {
+				let mut contents = contents.borrow_mut();
+				contents.clear();
+				contents.push_str(text);
+				true
+			}
+			None => false,
+		}
+	}
+
+	/// If this node is a processing instruction, return a reference to its target and data.
+	#[inline]
+	pub fn as_processing_instruction(&self) -> Option<&RefCell<(String, String)>> {
+		match self.data {
+			NodeData::ProcessingInstruction(ref value) => Some(value),
+			_ => None,
+		}
+	}
+
+	/// If this node is a processing instruction, return a copy of its target.
+	///
+	/// For `<?xml-stylesheet href="style.css"?>` this is `xml-stylesheet`.
+	#[inline]
+	pub fn processing_instruction_target(&self) -> Option<String> {
+		self.as_processing_instruction()
+			.map(|contents| contents.borrow().0.clone())
+	}
+
+	/// If this node is a processing instruction, return a copy of its data.
+	///
+	/// For `<?xml-stylesheet href="style.css"?>` this is `href="style.css"`.
+	#[inline]
+	pub fn processing_instruction_data(&self) -> Option<String> {
+		self.as_processing_instruction()
+			.map(|contents| contents.borrow().1.clone())
+	}
+
+	/// If this node is a doctype, return a reference to doctype-specific data.
 	#[inline]
 	pub fn as_doctype(&self) -> Option<&Doctype> {
 		match self.data {
@@ -333,6 +714,12 @@ impl Node {
 		}
 	}
 
+	/// Return whether this node is a document fragment, such as the contents of a template.
+	#[inline]
+	pub fn is_document_fragment(&self) -> bool {
+		matches!(self.data, NodeData::DocumentFragment)
+	}
+
 	/// Return a reference to the parent node, unless this node is the root of the tree.
 	#[inline]
 	pub fn parent(&self) -> Option<NodeRef> {
@@ -366,7 +753,15 @@ impl Node {
 	/// Detach a node from its parent and siblings. Children are not affected.
 	///
 	/// To remove a node and its descendants, detach it and drop any strong reference to it.
+	#[inline]
 	pub fn detach(&self) {
+		self.try_detach();
+	}
+
+	/// Detach a node from its parent and siblings, like `detach`.
+	///
+	/// Return whether the node had a parent, i.e. whether it was actually removed from a tree.
+	pub fn try_detach(&self) -> bool {
 		let parent_weak = self.parent.take();
 		let previous_sibling_weak = self.previous_sibling.take();
 		let next_sibling_strong = self.next_sibling.take();
@@ -394,14 +789,83 @@ impl Node {
 				parent_strong.first_child.replace(next_sibling_strong);
 			}
 		}
+
+		parent_weak.is_some()
+	}
+
+	/// Detach all children of this node, like setting the DOM `textContent` to an empty string.
+	///
+	/// Each child keeps its own descendants and becomes the root of a separate tree.
+	/// Unlike calling `detach` on each child, this does not relink the remaining siblings.
+	/// For `<template>` elements, template contents are not affected.
+	pub fn detach_children(&self) {
+		self.last_child.take();
+		let mut next = self.first_child.take();
+		while let Some(child) = next {
+			child.parent.take();
+			child.previous_sibling.take();
+			next = child.next_sibling.take();
+		}
 	}
 }
 
 impl NodeRef {
+	/// Panic if `node` is this node or one of its ancestors,
+	/// which inserting it here would make the tree contain itself and leak.
+	///
+	/// Ancestors include the template elements owning template contents on the way up.
+	fn assert_can_insert(&self, node: &NodeRef) {
+		assert!(
+			*node != *self && !node.contains_through_templates(self),
+			"cannot insert a node into itself or one of its descendants"
+		);
+	}
+
+	/// Return whether `other` is this node or one of its descendants,
+	/// counting the template contents of this node’s template elements as their descendants.
+	fn contains_through_templates(&self, other: &NodeRef) -> bool {
+		let owns_template = |node: &NodeRef| {
+			node.as_element()
+				.is_some_and(|element| element.template_contents.is_some())
+		};
+		// A leaf is no other node’s ancestor,
+		// which keeps inserting new nodes, as the parser does, from walking up the tree.
+		if self.first_child.is_none() && !owns_template(self) {
+			return false;
+		}
+		let mut root = other.clone();
+		for ancestor in other.inclusive_ancestors() {
+			if ancestor == *self {
+				return true;
+			}
+			root = ancestor;
+		}
+		if !root.is_document_fragment() {
+			return false;
+		}
+		// Template contents have no link to their template: look for it among this node’s own.
+		let mut stack = vec![self.clone()];
+		while let Some(node) = stack.pop() {
+			if let Some(contents) = node.as_element().and_then(|e| e.template_contents.as_ref()) {
+				if *contents == root {
+					return true;
+				}
+				stack.push(contents.clone());
+			}
+			stack.extend(node.children());
+		}
+		false
+	}
+
 	/// Append a new child to this node, after existing children.
 	///
 	/// The new child is detached from its previous position.
+	///
+	/// # Panics
+	///
+	/// If the new child is this node or one of its ancestors.
 	pub fn append(&self, new_child: NodeRef) {
+		self.assert_can_insert(&new_child);
 		new_child.detach();
 		new_child.parent.replace(Some(Rc::downgrade(&self.0)));
 		if let Some(last_child_weak) = self.last_child.replace(Some(Rc::downgrade(&new_child.0))) {
@@ -419,7 +883,12 @@ impl NodeRef {
 	/// Prepend a new child to this node, before existing children.
 	///
 	/// The new child is detached from its previous position.
+	///
+	/// # Panics
+	///
+	/// If the new child is this node or one of its ancestors.
 	pub fn prepend(&self, new_child: NodeRef) {
+		self.assert_can_insert(&new_child);
 		new_child.detach();
 		new_child.parent.replace(Some(Rc::downgrade(&self.0)));
 		if let Some(first_child) = self.first_child.take() {
@@ -438,7 +907,12 @@ impl NodeRef {
 	/// Insert a new sibling after this node.
 	///
 	/// The new sibling is detached from its previous position.
+	///
+	/// # Panics
+	///
+	/// If the new sibling is this node or one of its ancestors.
 	pub fn insert_after(&self, new_sibling: NodeRef) {
+		self.assert_can_insert(&new_sibling);
 		new_sibling.detach();
 		new_sibling.parent.replace(self.parent.clone_inner());
 		new_sibling
@@ -462,7 +936,12 @@ impl NodeRef {
 	/// Insert a new sibling before this node.
 	///
 	/// The new sibling is detached from its previous position.
+	///
+	/// # Panics
+	///
+	/// If the new sibling is this node or one of its ancestors.
 	pub fn insert_before(&self, new_sibling: NodeRef) {
+		self.assert_can_insert(&new_sibling);
 		new_sibling.detach();
 		new_sibling.parent.replace(self.parent.clone_inner());
 		new_sibling.next_sibling.replace(Some(self.0.clone()));
@@ -484,4 +963,75 @@ impl NodeRef {
 			parent.first_child.replace(Some(new_sibling.0));
 		}
 	}
+
+	/// Replace this node with `replacement` at the same position among its siblings,
+	/// and detach this node.
+	///
+	/// `replacement` is detached from its previous position first.
+	/// Nothing happens if this node has no parent, or if `replacement` is this node.
+	///
+	/// # Panics
+	///
+	/// If `replacement` is one of this node’s ancestors.
+	pub fn replace_with(&self, replacement: NodeRef) {
+		if self.parent().is_none() || replacement == *self {
+			return;
+		}
+		self.insert_before(replacement);
+		self.detach();
+	}
+
+	/// Put `wrapper` in this node’s position and move this node inside it,
+	/// after any children `wrapper` already has.
+	///
+	/// `wrapper` is detached from its previous position first.
+	///
+	/// # Panics
+	///
+	/// If `wrapper` is this node or one of its ancestors.
+	pub fn wrap(&self, wrapper: NodeRef) {
+		if self.parent().is_some() {
+			self.insert_before(wrapper.clone());
+		} else {
+			wrapper.detach();
+		}
+		wrapper.append(self.clone());
+	}
+
+	/// Replace this node with its children, then detach it.
+	///
+	/// Nothing happens if this node has no parent.
+	pub fn unwrap(&self) {
+		if self.parent().is_none() {
+			return;
+		}
+		for child in self.children() {
+			self.insert_before(child);
+		}
+		self.detach();
+	}
+
+	/// Replace the children of this node with `new_children`, in order,
+	/// like the DOM `replaceChildren`.
+	///
+	/// Each new child is detached from its previous position,
+	/// which may be among the current children of this node.
+	///
+	/// # Panics
+	///
+	/// If one of `new_children` is this node or one of its ancestors,
+	/// since the tree would then contain itself. This is checked before any node is moved.
+	pub fn replace_children<I>(&self, new_children: I)
+	where
+		I: IntoIterator<Item = NodeRef>,
+	{
+		let new_children = new_children.into_iter().collect::<Vec<_>>();
+		for new_child in &new_children {
+			self.assert_can_insert(new_child);
+		}
+		self.detach_children();
+		for new_child in new_children {
+			self.append(new_child);
+		}
+	}
 }